@@ -1,3 +1,5 @@
+use crate::viewport::BoundingBox;
+use std::collections::HashMap;
 use std::fmt;
 use std::num::NonZeroI64;
 
@@ -57,7 +59,8 @@ pub struct NodeData {
     pub lon: f64,
     /// Guaranteed to be sorted
     pub tags: Vec<(String, String)>,
-    pub way: Option<NonZeroI64>,
+    /// the ways referencing this node, each only once
+    pub ways: Vec<NonZeroI64>,
 }
 #[derive(Debug, Clone)]
 pub struct WayData {
@@ -75,7 +78,7 @@ impl WayData {
 
         WayData {
             wid,
-            is_closed: refs.get(0).unwrap() == refs.get(refs.len() - 1).unwrap()
+            is_closed: refs.first().unwrap() == refs.last().unwrap()
                 || tags
                     .iter()
                     .any(|(tag_key, tag_value)| tag_key == "area" && tag_value == "yes"),
@@ -138,7 +141,109 @@ impl ElementData for RelationData {
         self.into()
     }
 
+    /// Multipolygons enclose an area, any other relation is a collection of elements.
     fn has_closed_path(&self) -> bool {
-        todo!();
+        self.tags
+            .iter()
+            .any(|(tag_key, tag_value)| tag_key == "type" && tag_value == "multipolygon")
+    }
+}
+
+impl From<&RelationMemberType> for ElementID {
+    fn from(val: &RelationMemberType) -> Self {
+        match *val {
+            RelationMemberType::Relation(rid) => ElementID::Relation(rid),
+            RelationMemberType::Node(nid) => ElementID::Node(nid),
+            RelationMemberType::Way(wid) => ElementID::Way(wid),
+        }
+    }
+}
+
+/// Borrows all extracted elements, so that conditions can look at the neighbours of an element.
+#[derive(Debug, Clone)]
+pub struct DataSet<'a> {
+    pub nid_to_node_data: &'a HashMap<NonZeroI64, NodeData>,
+    pub wid_to_way_data: &'a HashMap<NonZeroI64, WayData>,
+    pub rid_to_relation_data: &'a HashMap<NonZeroI64, RelationData>,
+    /// the relations each element is a member of, with the index of the membership
+    element_to_parent_relations: HashMap<ElementID, Vec<(NonZeroI64, usize)>>,
+}
+
+impl<'a> DataSet<'a> {
+    pub fn new(
+        nid_to_node_data: &'a HashMap<NonZeroI64, NodeData>,
+        wid_to_way_data: &'a HashMap<NonZeroI64, WayData>,
+        rid_to_relation_data: &'a HashMap<NonZeroI64, RelationData>,
+    ) -> DataSet<'a> {
+        let mut element_to_parent_relations: HashMap<ElementID, Vec<(NonZeroI64, usize)>> =
+            HashMap::new();
+
+        for relation_data in rid_to_relation_data.values() {
+            for (index, member) in relation_data.members.iter().enumerate() {
+                element_to_parent_relations
+                    .entry((&member.member).into())
+                    .or_default()
+                    .push((relation_data.rid, index));
+            }
+        }
+
+        DataSet {
+            nid_to_node_data,
+            wid_to_way_data,
+            rid_to_relation_data,
+            element_to_parent_relations,
+        }
+    }
+
+    /// Returns the relations the element is a member of, together with the index of the member.
+    pub fn parent_relations(
+        &self,
+        id: &ElementID,
+    ) -> impl Iterator<Item = (&'a RelationData, usize)> + '_ {
+        self.element_to_parent_relations
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |(rid, index)| {
+                self.rid_to_relation_data
+                    .get(rid)
+                    .map(|relation_data| (relation_data, *index))
+            })
+    }
+
+    /// Returns the center of the bounding box of the element, or `None` if none of its nodes
+    /// are part of the data set. Members of relations are not resolved recursively.
+    pub fn center(&self, id: &ElementID) -> Option<(f64, f64)> {
+        let way_nodes = |wid: &NonZeroI64| {
+            self.wid_to_way_data
+                .get(wid)
+                .into_iter()
+                .flat_map(|way_data| way_data.refs())
+        };
+
+        let nids: Vec<NonZeroI64> = match id {
+            ElementID::Canvas => return None,
+            ElementID::Node(nid) => vec![*nid],
+            ElementID::Way(wid) => way_nodes(wid).copied().collect(),
+            ElementID::Relation(rid) => self
+                .rid_to_relation_data
+                .get(rid)
+                .into_iter()
+                .flat_map(|relation_data| &relation_data.members)
+                .flat_map(|member| match &member.member {
+                    RelationMemberType::Node(nid) => vec![*nid],
+                    RelationMemberType::Way(wid) => way_nodes(wid).copied().collect(),
+                    RelationMemberType::Relation(_) => vec![],
+                })
+                .collect(),
+        };
+
+        let bounding_box =
+            BoundingBox::from_nodes(nids.iter().filter_map(|nid| self.nid_to_node_data.get(nid)))?;
+
+        Some((
+            (bounding_box.min_lat + bounding_box.max_lat) / 2.0,
+            (bounding_box.min_lon + bounding_box.max_lon) / 2.0,
+        ))
     }
 }
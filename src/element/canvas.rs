@@ -1,14 +1,15 @@
 use crate::data::{ElementData, ElementID};
+use crate::elevation::hillshade::HillshadeSettings;
 use crate::mapcss::declaration::{
     MapCssDeclarationList, MapCssDeclarationProperty, MapCssDeclarationValueType, ToColorValue,
-    RGBA,
+    ToFloatValue, RGBA,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct CanvasElement {}
 
-impl Into<ElementID> for CanvasElement {
-    fn into(self) -> ElementID {
+impl From<CanvasElement> for ElementID {
+    fn from(_: CanvasElement) -> Self {
         ElementID::Canvas
     }
 }
@@ -42,4 +43,46 @@ impl CanvasElement {
             )
             .to_color()
     }
+
+    pub fn hillshade_settings(
+        self,
+        mapcss_declarations: &MapCssDeclarationList,
+    ) -> HillshadeSettings {
+        let defaults = HillshadeSettings::default();
+
+        let search_or_default = |property, default| {
+            mapcss_declarations
+                .search_or_default(
+                    Box::new(CanvasElement {}),
+                    &property,
+                    &MapCssDeclarationValueType::Float(default),
+                )
+                .to_float()
+        };
+
+        HillshadeSettings {
+            opacity: search_or_default(
+                MapCssDeclarationProperty::HillshadeOpacity,
+                defaults.opacity,
+            )
+            .clamp(0.0, 1.0),
+            azimuth: search_or_default(
+                MapCssDeclarationProperty::HillshadeAzimuth,
+                defaults.azimuth,
+            ),
+            altitude: search_or_default(
+                MapCssDeclarationProperty::HillshadeAltitude,
+                defaults.altitude,
+            ),
+        }
+    }
+
+    /// Returns the interval between two contour lines in meters, if contour lines should be
+    /// generated from the elevation data.
+    pub fn contour_interval(self, mapcss_declarations: &MapCssDeclarationList) -> Option<f64> {
+        mapcss_declarations
+            .search_cascading(Box::new(self), &MapCssDeclarationProperty::ContourInterval)
+            .map(|interval| interval.to_float())
+            .filter(|interval| *interval > 0.0)
+    }
 }
@@ -0,0 +1,231 @@
+use crate::data::{DataSet, ElementData};
+use crate::mapcss::declaration::{
+    MapCssDeclarationList, MapCssDeclarationProperty, MapCssDeclarationValueType,
+    TextAnchorHorizontalDeclarationVariant, TextAnchorVerticalDeclarationVariant, ToFloatValue,
+    ToIntegerValue,
+};
+use crate::mapcss::parser::FloatSize;
+
+/// How the label of an element is placed around its point, e.g. a node or the center of an area,
+/// and broken into lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelPlacement {
+    pub anchor_horizontal: TextAnchorHorizontalDeclarationVariant,
+    pub anchor_vertical: TextAnchorVerticalDeclarationVariant,
+    /// in pixels, positive values move the label down
+    pub offset_y: FloatSize,
+    /// the maximum width of a line in pixels, labels are not wrapped if unset
+    pub wrap_width: Option<FloatSize>,
+    /// the character at which lines may be broken, it is dropped at the line breaks
+    pub wrap_character: char,
+}
+
+impl Default for LabelPlacement {
+    fn default() -> Self {
+        LabelPlacement {
+            anchor_horizontal: TextAnchorHorizontalDeclarationVariant::default(),
+            anchor_vertical: TextAnchorVerticalDeclarationVariant::default(),
+            offset_y: 0.0,
+            wrap_width: None,
+            wrap_character: ' ',
+        }
+    }
+}
+
+impl LabelPlacement {
+    /// Resolves the text-anchor-horizontal, text-anchor-vertical, text-offset-y, text-wrap-width
+    /// and text-wrap-character declarations of the element in the given layer.
+    pub fn new<E: ElementData + Clone + 'static>(
+        element_data: &E,
+        mapcss_declarations: &MapCssDeclarationList,
+        data_set: Option<&DataSet<'_>>,
+        layer: &str,
+    ) -> LabelPlacement {
+        let defaults = LabelPlacement::default();
+
+        let search = |property| {
+            mapcss_declarations.search_cascading_in_layer(
+                Box::new(element_data.clone()),
+                data_set,
+                layer,
+                &property,
+            )
+        };
+
+        LabelPlacement {
+            anchor_horizontal: match search(MapCssDeclarationProperty::TextAnchorHorizontal)
+                .as_deref()
+            {
+                Some(MapCssDeclarationValueType::TextAnchorHorizontalDeclarationVariant(
+                    anchor,
+                )) => *anchor,
+                _ => defaults.anchor_horizontal,
+            },
+            anchor_vertical: match search(MapCssDeclarationProperty::TextAnchorVertical).as_deref()
+            {
+                Some(MapCssDeclarationValueType::TextAnchorVerticalDeclarationVariant(anchor)) => {
+                    *anchor
+                }
+                _ => defaults.anchor_vertical,
+            },
+            offset_y: search(MapCssDeclarationProperty::TextOffsetY)
+                .map(|offset| offset.to_float())
+                .unwrap_or(defaults.offset_y),
+            wrap_width: search(MapCssDeclarationProperty::TextWrapWidth)
+                .map(|width| width.to_integer() as FloatSize)
+                .filter(|width| *width > 0.0),
+            wrap_character: match search(MapCssDeclarationProperty::TextWrapCharacter).as_deref() {
+                Some(MapCssDeclarationValueType::String(character)) => {
+                    character.chars().next().unwrap_or(defaults.wrap_character)
+                }
+                _ => defaults.wrap_character,
+            },
+        }
+    }
+
+    /// Returns the top left corner of a label of the given size, both in pixels, whose element
+    /// is located at the given point of the image.
+    pub fn top_left(&self, point: (f64, f64), label_size: (f64, f64)) -> (f64, f64) {
+        let (x, y) = point;
+        let (width, height) = label_size;
+
+        let left = match self.anchor_horizontal {
+            TextAnchorHorizontalDeclarationVariant::Left => x - width,
+            TextAnchorHorizontalDeclarationVariant::Center => x - width / 2.0,
+            TextAnchorHorizontalDeclarationVariant::Right => x,
+        };
+        let top = match self.anchor_vertical {
+            TextAnchorVerticalDeclarationVariant::Above => y - height,
+            TextAnchorVerticalDeclarationVariant::Center => y - height / 2.0,
+            TextAnchorVerticalDeclarationVariant::Below => y,
+        };
+
+        (left, top + self.offset_y)
+    }
+
+    /// Breaks the text into lines at the wrap character, so that no line is wider than the wrap
+    /// width unless it consists of a single word. The width of a text in pixels depends on the
+    /// font and is measured by the given function.
+    pub fn wrap(&self, text: &str, text_width: impl Fn(&str) -> f64) -> Vec<String> {
+        let Some(wrap_width) = self.wrap_width else {
+            return vec![text.to_owned()];
+        };
+
+        let mut lines: Vec<String> = Vec::new();
+
+        for word in text.split(self.wrap_character) {
+            match lines.last_mut() {
+                Some(line)
+                    if text_width(&format!("{}{}{}", line, self.wrap_character, word))
+                        <= wrap_width =>
+                {
+                    line.push(self.wrap_character);
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_owned()),
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LabelPlacement;
+    use crate::data::NodeData;
+    use crate::mapcss::declaration::{
+        MapCssDeclarationList, TextAnchorHorizontalDeclarationVariant,
+        TextAnchorVerticalDeclarationVariant,
+    };
+    use crate::mapcss::parser::MapCssParser;
+    use crate::mapcss::selectors::DEFAULT_LAYER;
+    use std::num::NonZeroI64;
+
+    #[test]
+    fn test_resolve_label_placement() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            node[highway=motorway_junction] {
+                text-anchor-horizontal: right;
+                text-anchor-vertical: above;
+                text-offset-y: -2;
+                text-wrap-width: 30;
+                text-wrap-character: ";";
+            }
+            "#,
+        )
+        .unwrap();
+        let mapcss_declarations = MapCssDeclarationList::new(rules);
+
+        let node = |tags: Vec<(String, String)>| NodeData {
+            nid: NonZeroI64::new(1).unwrap(),
+            lat: 47.0,
+            lon: 9.0,
+            tags,
+            ways: vec![],
+        };
+
+        let junction = node(vec![("highway".into(), "motorway_junction".into())]);
+        assert_eq!(
+            LabelPlacement::new(&junction, &mapcss_declarations, None, DEFAULT_LAYER),
+            LabelPlacement {
+                anchor_horizontal: TextAnchorHorizontalDeclarationVariant::Right,
+                anchor_vertical: TextAnchorVerticalDeclarationVariant::Above,
+                offset_y: -2.0,
+                wrap_width: Some(30.0),
+                wrap_character: ';',
+            }
+        );
+
+        assert_eq!(
+            LabelPlacement::new(&node(vec![]), &mapcss_declarations, None, DEFAULT_LAYER),
+            LabelPlacement::default()
+        );
+    }
+
+    #[test]
+    fn test_place_label() {
+        let mut placement = LabelPlacement::default();
+        assert_eq!(
+            placement.top_left((100.0, 50.0), (40.0, 10.0)),
+            (80.0, 45.0)
+        );
+
+        placement.anchor_horizontal = TextAnchorHorizontalDeclarationVariant::Left;
+        placement.anchor_vertical = TextAnchorVerticalDeclarationVariant::Below;
+        placement.offset_y = 2.0;
+        assert_eq!(
+            placement.top_left((100.0, 50.0), (40.0, 10.0)),
+            (60.0, 52.0)
+        );
+
+        placement.anchor_horizontal = TextAnchorHorizontalDeclarationVariant::Right;
+        placement.anchor_vertical = TextAnchorVerticalDeclarationVariant::Above;
+        assert_eq!(
+            placement.top_left((100.0, 50.0), (40.0, 10.0)),
+            (100.0, 42.0)
+        );
+    }
+
+    #[test]
+    fn test_wrap_label() {
+        // every character is 5 pixels wide
+        let text_width = |text: &str| text.chars().count() as f64 * 5.0;
+        let mut placement = LabelPlacement::default();
+
+        assert_eq!(placement.wrap("Vaduz Nord", text_width), ["Vaduz Nord"]);
+
+        placement.wrap_width = Some(50.0);
+        assert_eq!(
+            placement.wrap("Schaan Vaduz Nord Liechtenstein", text_width),
+            ["Schaan", "Vaduz Nord", "Liechtenstein"]
+        );
+
+        placement.wrap_character = ';';
+        assert_eq!(
+            placement.wrap("A 13;E 43;Vaduz", text_width),
+            ["A 13;E 43", "Vaduz"]
+        );
+    }
+}
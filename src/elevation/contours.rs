@@ -0,0 +1,295 @@
+use super::{ElevationModel, ElevationTile};
+use crate::data::{NodeData, WayData};
+use crate::viewport::BoundingBox;
+use std::collections::HashMap;
+use std::num::NonZeroI64;
+
+/// Identifies the point where a contour line crosses an edge between two neighbouring samples
+/// of a tile: the level index, whether the edge is horizontal and the row and column of the
+/// sample it starts at.
+type EdgeKey = (usize, bool, usize, usize);
+
+/// Generates contour lines every `interval` meters from the elevation data covering `extent`.
+///
+/// They are returned as synthetic nodes and ways (with negative IDs, like elements that were not
+/// uploaded to OSM yet), the ways being tagged with `contour=elevation` and `ele=<elevation>`.
+pub fn generate_contour_lines(
+    elevation_model: &ElevationModel,
+    extent: &BoundingBox,
+    interval: f64,
+) -> (Vec<NodeData>, Vec<WayData>) {
+    assert!(interval > 0.0, "The contour interval must be positive!");
+
+    let mut nodes = Vec::new();
+    let mut ways = Vec::new();
+
+    for tile in &elevation_model.tiles {
+        trace_tile(tile, extent, interval, &mut nodes, &mut ways);
+    }
+
+    (nodes, ways)
+}
+
+fn trace_tile(
+    tile: &ElevationTile,
+    extent: &BoundingBox,
+    interval: f64,
+    nodes: &mut Vec<NodeData>,
+    ways: &mut Vec<WayData>,
+) {
+    let to_row =
+        |lat: f64| ((tile.north - lat) / tile.lat_step).clamp(0.0, (tile.height - 1) as f64);
+    let to_column =
+        |lon: f64| ((lon - tile.west) / tile.lon_step).clamp(0.0, (tile.width - 1) as f64);
+
+    let first_row = to_row(extent.max_lat).floor() as usize;
+    let last_row = to_row(extent.min_lat).ceil() as usize;
+    let first_column = to_column(extent.min_lon).floor() as usize;
+    let last_column = to_column(extent.max_lon).ceil() as usize;
+
+    let sample = |row: usize, column: usize| tile.samples[row * tile.width + column] as f64;
+    let position = |row: usize, column: usize| {
+        (
+            tile.north - row as f64 * tile.lat_step,
+            tile.west + column as f64 * tile.lon_step,
+        )
+    };
+
+    // the level of every level index that has segments
+    let mut levels: HashMap<usize, f64> = HashMap::new();
+    let mut segments: Vec<(EdgeKey, EdgeKey)> = Vec::new();
+    let mut crossings: HashMap<EdgeKey, (f64, f64)> = HashMap::new();
+
+    for row in first_row..last_row {
+        for column in first_column..last_column {
+            let corners = [
+                (row, column),
+                (row, column + 1),
+                (row + 1, column + 1),
+                (row + 1, column),
+            ];
+            let values = corners.map(|(row, column)| sample(row, column));
+
+            if values.iter().any(|value| value.is_nan()) {
+                continue;
+            }
+
+            let min = values.iter().copied().fold(f64::MAX, f64::min);
+            let max = values.iter().copied().fold(f64::MIN, f64::max);
+
+            let mut level_index = (min / interval).ceil() as i64;
+
+            while level_index as f64 * interval <= max {
+                let level = level_index as f64 * interval;
+                // level indices are used as keys, the lowest elevation on earth is > -11 km
+                let key_index = (level_index + (11_000.0 / interval) as i64) as usize;
+                levels.insert(key_index, level);
+
+                // top, right, bottom and left edge of the cell
+                let edges: [EdgeKey; 4] = [
+                    (key_index, true, row, column),
+                    (key_index, false, row, column + 1),
+                    (key_index, true, row + 1, column),
+                    (key_index, false, row, column),
+                ];
+                // the corners the edges run between, as indices into `corners`
+                const EDGE_CORNERS: [(usize, usize); 4] = [(0, 1), (1, 2), (3, 2), (0, 3)];
+
+                let case = values
+                    .iter()
+                    .fold(0, |case, value| (case << 1) | (*value >= level) as usize);
+
+                let center_is_above = values.iter().sum::<f64>() / 4.0 >= level;
+
+                // pairs of edges (top = 0, right = 1, bottom = 2, left = 3) that are connected
+                // by a segment, where `case` has a bit set for every corner (top left, top right,
+                // bottom right, bottom left) that is at or above the level
+                let edge_pairs: &[(usize, usize)] = match case {
+                    0b0000 | 0b1111 => &[],
+                    0b0001 | 0b1110 => &[(3, 2)],
+                    0b0010 | 0b1101 => &[(2, 1)],
+                    0b0011 | 0b1100 => &[(3, 1)],
+                    0b0100 | 0b1011 => &[(0, 1)],
+                    0b0110 | 0b1001 => &[(0, 2)],
+                    0b0111 | 0b1000 => &[(3, 0)],
+                    // saddles are resolved by the average of the cell
+                    0b0101 if center_is_above => &[(3, 0), (2, 1)],
+                    0b0101 => &[(0, 1), (3, 2)],
+                    0b1010 if center_is_above => &[(0, 1), (3, 2)],
+                    0b1010 => &[(3, 0), (2, 1)],
+                    _ => unreachable!(),
+                };
+
+                for &(edge_a, edge_b) in edge_pairs {
+                    for edge in [edge_a, edge_b] {
+                        crossings.entry(edges[edge]).or_insert_with(|| {
+                            let (corner_a, corner_b) = EDGE_CORNERS[edge];
+                            let (lat_a, lon_a) = position(corners[corner_a].0, corners[corner_a].1);
+                            let (lat_b, lon_b) = position(corners[corner_b].0, corners[corner_b].1);
+                            let t =
+                                (level - values[corner_a]) / (values[corner_b] - values[corner_a]);
+
+                            (lat_a + (lat_b - lat_a) * t, lon_a + (lon_b - lon_a) * t)
+                        });
+                    }
+
+                    segments.push((edges[edge_a], edges[edge_b]));
+                }
+
+                level_index += 1;
+            }
+        }
+    }
+
+    for edge_keys in join_segments(&segments) {
+        let level = levels[&edge_keys[0].0];
+        let wid = next_synthetic_id(ways.len());
+
+        let mut refs = Vec::with_capacity(edge_keys.len());
+        let mut node_ids: HashMap<EdgeKey, NonZeroI64> = HashMap::new();
+
+        for edge_key in &edge_keys {
+            let nid = *node_ids.entry(*edge_key).or_insert_with(|| {
+                let nid = next_synthetic_id(nodes.len());
+                let (lat, lon) = crossings[edge_key];
+
+                nodes.push(NodeData {
+                    nid,
+                    lat,
+                    lon,
+                    tags: vec![],
+                    ways: vec![wid],
+                });
+
+                nid
+            });
+
+            refs.push(nid);
+        }
+
+        let tags = vec![
+            ("contour".to_owned(), "elevation".to_owned()),
+            ("ele".to_owned(), level.to_string()),
+        ];
+
+        ways.push(WayData::new(wid, tags, refs));
+    }
+}
+
+/// Joins segments sharing an edge into lines, returning the edges each line passes.
+fn join_segments(segments: &[(EdgeKey, EdgeKey)]) -> Vec<Vec<EdgeKey>> {
+    let mut segments_by_edge: HashMap<EdgeKey, Vec<usize>> = HashMap::new();
+
+    for (index, (edge_a, edge_b)) in segments.iter().enumerate() {
+        segments_by_edge.entry(*edge_a).or_default().push(index);
+        segments_by_edge.entry(*edge_b).or_default().push(index);
+    }
+
+    let mut is_used = vec![false; segments.len()];
+    let mut lines = Vec::new();
+
+    // follows the unused segments starting at `edge` and returns the edges passed
+    let follow = |mut edge: EdgeKey, is_used: &mut Vec<bool>| {
+        let mut edges = Vec::new();
+
+        while let Some(&index) = segments_by_edge[&edge]
+            .iter()
+            .find(|&&index| !is_used[index])
+        {
+            is_used[index] = true;
+
+            let (edge_a, edge_b) = segments[index];
+            edge = if edge_a == edge { edge_b } else { edge_a };
+            edges.push(edge);
+        }
+
+        edges
+    };
+
+    for index in 0..segments.len() {
+        if is_used[index] {
+            continue;
+        }
+
+        is_used[index] = true;
+        let (start, end) = segments[index];
+
+        let mut line = follow(start, &mut is_used);
+        line.reverse();
+        line.push(start);
+        line.push(end);
+        line.extend(follow(end, &mut is_used));
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Returns the `index`th synthetic ID, counting downwards from -1.
+fn next_synthetic_id(index: usize) -> NonZeroI64 {
+    NonZeroI64::new(-(index as i64) - 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_contour_lines;
+    use crate::data::ElementData;
+    use crate::elevation::{ElevationModel, ElevationTile};
+    use crate::viewport::BoundingBox;
+
+    #[test]
+    fn test_contour_lines_around_a_hill() {
+        // a hill of 100 m in the center of a 5x5 grid, dropping to 0 m at the border
+        #[rustfmt::skip]
+        let samples = vec![
+            0.0,  0.0,   0.0,  0.0, 0.0,
+            0.0, 50.0,  50.0, 50.0, 0.0,
+            0.0, 50.0, 100.0, 50.0, 0.0,
+            0.0, 50.0,  50.0, 50.0, 0.0,
+            0.0,  0.0,   0.0,  0.0, 0.0,
+        ];
+
+        let elevation_model = ElevationModel::new(vec![ElevationTile::new(
+            47.04, 9.0, 0.01, 0.01, 5, 5, samples,
+        )]);
+
+        let extent = BoundingBox {
+            min_lat: 47.0,
+            max_lat: 47.04,
+            min_lon: 9.0,
+            max_lon: 9.04,
+        };
+
+        let (nodes, ways) = generate_contour_lines(&elevation_model, &extent, 40.0);
+
+        // one ring at 40 m and one at 80 m
+        assert_eq!(ways.len(), 2);
+
+        for way in &ways {
+            assert_eq!(way.refs().first(), way.refs().last());
+            assert!(way.has_closed_path());
+            assert!(way.way_id().get() < 0);
+            assert!(way.tags().contains(&("contour".into(), "elevation".into())));
+        }
+
+        let mut elevations = ways
+            .iter()
+            .map(|way| {
+                way.tags()
+                    .iter()
+                    .find(|(key, _)| key == "ele")
+                    .unwrap()
+                    .1
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        elevations.sort();
+        assert_eq!(elevations, ["40", "80"]);
+
+        // all nodes lie within the hill
+        assert!(nodes
+            .iter()
+            .all(|node| node.lat > 47.0 && node.lat < 47.04 && node.lon > 9.0 && node.lon < 9.04));
+    }
+}
@@ -0,0 +1,129 @@
+use super::{ElevationError, ElevationTile};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+const MODEL_TYPE_GEO_KEY: u16 = 1024;
+const RASTER_TYPE_GEO_KEY: u16 = 1025;
+
+const MODEL_TYPE_GEOGRAPHIC: u16 = 2;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+/// Reads a single band GeoTIFF whose raster is georeferenced in geographic (latitude and
+/// longitude) coordinates, as it is the case for e.g. SRTM or Copernicus DEM downloads.
+pub fn read_geotiff(file_path: &Path) -> Result<ElevationTile, ElevationError> {
+    let mut decoder =
+        Decoder::new(BufReader::new(File::open(file_path)?))?.with_limits(Limits::unlimited());
+
+    if !matches!(decoder.colortype()?, ColorType::Gray(_)) {
+        return Err(ElevationError::InvalidData(
+            "the GeoTIFF must contain a single band of elevations".to_owned(),
+        ));
+    }
+
+    let (width, height) = decoder.dimensions()?;
+
+    // interpolating elevations requires at least two rows and columns of samples
+    if width < 2 || height < 2 {
+        return Err(ElevationError::InvalidData(format!(
+            "the GeoTIFF of {}x{} pixels is too small, it needs at least 2x2 pixels",
+            width, height
+        )));
+    }
+
+    let pixel_scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+    let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+
+    if pixel_scale.len() < 2 || tiepoint.len() < 6 {
+        return Err(ElevationError::InvalidData(
+            "the GeoTIFF lacks a valid pixel scale or tiepoint".to_owned(),
+        ));
+    }
+
+    let geo_keys = match decoder.find_tag(Tag::GeoKeyDirectoryTag)? {
+        Some(value) => value.into_u16_vec()?,
+        None => Vec::new(),
+    };
+
+    if let Some(model_type) = find_geo_key(&geo_keys, MODEL_TYPE_GEO_KEY) {
+        if model_type != MODEL_TYPE_GEOGRAPHIC {
+            return Err(ElevationError::InvalidData(
+                "only GeoTIFFs in geographic coordinates (e.g. EPSG:4326) are supported".to_owned(),
+            ));
+        }
+    }
+
+    // by default, the tiepoint refers to the corner of a pixel and not to its center
+    let pixel_center_offset = match find_geo_key(&geo_keys, RASTER_TYPE_GEO_KEY) {
+        Some(RASTER_PIXEL_IS_POINT) => 0.0,
+        _ => 0.5,
+    };
+
+    let no_data = match decoder.find_tag(Tag::GdalNodata)? {
+        Some(value) => value
+            .into_string()?
+            .trim_matches('\0')
+            .trim()
+            .parse::<f64>()
+            .ok(),
+        None => None,
+    };
+
+    let samples: Vec<f64> = match decoder.read_image()? {
+        DecodingResult::U8(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::U64(samples) => samples.into_iter().map(|x| x as f64).collect(),
+        DecodingResult::I8(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::I64(samples) => samples.into_iter().map(|x| x as f64).collect(),
+        DecodingResult::F32(samples) => samples.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(samples) => samples,
+    };
+
+    if samples.len() != width as usize * height as usize {
+        return Err(ElevationError::InvalidData(format!(
+            "the GeoTIFF contains {} samples instead of {}x{}",
+            samples.len(),
+            width,
+            height
+        )));
+    }
+
+    let samples = samples
+        .into_iter()
+        .map(|elevation| match no_data {
+            Some(no_data) if elevation == no_data => f32::NAN,
+            _ => elevation as f32,
+        })
+        .collect();
+
+    let (lon_step, lat_step) = (pixel_scale[0], pixel_scale[1]);
+    let (tiepoint_column, tiepoint_row) = (tiepoint[0], tiepoint[1]);
+    let (tiepoint_lon, tiepoint_lat) = (tiepoint[3], tiepoint[4]);
+
+    Ok(ElevationTile::new(
+        tiepoint_lat - (pixel_center_offset - tiepoint_row) * lat_step,
+        tiepoint_lon + (pixel_center_offset - tiepoint_column) * lon_step,
+        lat_step,
+        lon_step,
+        width as usize,
+        height as usize,
+        samples,
+    ))
+}
+
+/// Looks up the value of a GeoKey that is stored directly in the GeoKey directory.
+fn find_geo_key(geo_keys: &[u16], key_id: u16) -> Option<u16> {
+    // the header consists of four values, followed by entries of four values each:
+    // key id, tag location (0 if the value is stored inline), count and the value itself
+    geo_keys
+        .get(4..)?
+        .chunks_exact(4)
+        .find(|entry| entry[0] == key_id && entry[1] == 0)
+        .map(|entry| entry[3])
+}
@@ -0,0 +1,114 @@
+use super::{ElevationError, ElevationTile};
+use std::path::Path;
+
+/// The value SRTM uses for samples without data.
+const VOID_VALUE: i16 = -32768;
+
+/// Reads an SRTM `.hgt` file, whose name (e.g. `N47E009.hgt`) encodes the south west corner of
+/// the one by one degree tile it covers.
+pub fn read_hgt(file_path: &Path) -> Result<ElevationTile, ElevationError> {
+    let file_name = file_path
+        .file_stem()
+        .and_then(|file_stem| file_stem.to_str())
+        .unwrap_or_default();
+
+    let bytes = std::fs::read(file_path)?;
+
+    parse_hgt(file_name, &bytes)
+}
+
+/// Parses the contents of an `.hgt` file: a square grid of big endian 16 bit elevations in
+/// meters, row by row from north to south, whose edges overlap with the neighbouring tiles.
+pub fn parse_hgt(file_name: &str, bytes: &[u8]) -> Result<ElevationTile, ElevationError> {
+    let (south, west) = parse_south_west_corner(file_name).ok_or_else(|| {
+        ElevationError::InvalidData(format!(
+            "\"{}\" is not a valid SRTM tile name like N47E009",
+            file_name
+        ))
+    })?;
+
+    let sample_count = bytes.len() / 2;
+    let size = (sample_count as f64).sqrt().round() as usize;
+
+    if !bytes.len().is_multiple_of(2) || size * size != sample_count || size < 2 {
+        return Err(ElevationError::InvalidData(format!(
+            "{} bytes do not form a square grid of 16 bit samples",
+            bytes.len()
+        )));
+    }
+
+    let samples = bytes
+        .chunks_exact(2)
+        .map(|sample| match i16::from_be_bytes([sample[0], sample[1]]) {
+            VOID_VALUE => f32::NAN,
+            elevation => elevation as f32,
+        })
+        .collect();
+
+    let step = 1.0 / (size - 1) as f64;
+
+    Ok(ElevationTile::new(
+        south + 1.0,
+        west,
+        step,
+        step,
+        size,
+        size,
+        samples,
+    ))
+}
+
+/// Parses tile names like `N47E009` or `s12w077` into the latitude and longitude of their south
+/// west corner.
+fn parse_south_west_corner(file_name: &str) -> Option<(f64, f64)> {
+    let file_name = file_name.to_ascii_uppercase();
+
+    if file_name.len() != 7 || !file_name.is_ascii() {
+        return None;
+    }
+
+    let lat = file_name[1..3].parse::<u8>().ok()? as f64;
+    let lon = file_name[4..7].parse::<u8>().ok()? as f64;
+
+    let lat = match &file_name[0..1] {
+        "N" => lat,
+        "S" => -lat,
+        _ => return None,
+    };
+
+    let lon = match &file_name[3..4] {
+        "E" => lon,
+        "W" => -lon,
+        _ => return None,
+    };
+
+    Some((lat, lon))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_hgt;
+
+    #[test]
+    fn test_parse_hgt() {
+        // 3x3 samples covering N47E009 with a void in the south east corner
+        let bytes = [1000i16, 1100, 1200, 900, 1000, 1100, 800, 900, -32768]
+            .iter()
+            .flat_map(|elevation| elevation.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        let tile = parse_hgt("N47E009", &bytes).unwrap();
+
+        assert_eq!(tile.elevation_at(48.0, 9.0), Some(1000.0));
+        assert_eq!(tile.elevation_at(48.0, 10.0), Some(1200.0));
+        assert_eq!(tile.elevation_at(47.5, 9.5), Some(1000.0));
+        assert_eq!(tile.elevation_at(47.0, 9.0), Some(800.0));
+        assert_eq!(tile.elevation_at(47.0, 10.0), None);
+
+        assert!(parse_hgt("s12w077", &bytes).is_ok());
+        assert!(parse_hgt("liechtenstein", &bytes).is_err());
+        assert!(parse_hgt("N47E009", &bytes[..16]).is_err());
+        // a single sample is too small to interpolate between
+        assert!(parse_hgt("N47E009", &bytes[..2]).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use super::ElevationModel;
+
+/// Meters per degree of latitude (and of longitude at the equator).
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Slopes are never derived from samples closer than this (in meters), which roughly is the
+/// resolution of the most detailed freely available DEMs.
+const MIN_SAMPLE_DISTANCE: f64 = 30.0;
+
+/// Configures how the terrain is lit, set in the `canvas {}` block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HillshadeSettings {
+    /// how strongly the shading is blended into the background, from 0 to 1
+    pub opacity: f64,
+    /// direction the light is coming from, in degrees clockwise from north
+    pub azimuth: f64,
+    /// angle of the light above the horizon, in degrees
+    pub altitude: f64,
+}
+
+impl Default for HillshadeSettings {
+    fn default() -> Self {
+        HillshadeSettings {
+            opacity: 0.5,
+            azimuth: 315.0,
+            altitude: 45.0,
+        }
+    }
+}
+
+impl HillshadeSettings {
+    /// Returns how much light the terrain at the given location receives, from 0 (facing away
+    /// from the light) to 1 (facing the light directly). Slopes are derived from elevations
+    /// `sample_distance` meters apart.
+    pub fn illumination(
+        &self,
+        elevation_model: &ElevationModel,
+        lat: f64,
+        lon: f64,
+        sample_distance: f64,
+    ) -> Option<f64> {
+        let sample_distance = sample_distance.max(MIN_SAMPLE_DISTANCE);
+        let lat_offset = sample_distance / METERS_PER_DEGREE;
+        let lon_offset = sample_distance / (METERS_PER_DEGREE * lat.to_radians().cos());
+
+        // gradient of the terrain towards east and north
+        let dz_dx = (elevation_model.elevation_at(lat, lon + lon_offset)?
+            - elevation_model.elevation_at(lat, lon - lon_offset)?)
+            / (2.0 * sample_distance);
+        let dz_dy = (elevation_model.elevation_at(lat + lat_offset, lon)?
+            - elevation_model.elevation_at(lat - lat_offset, lon)?)
+            / (2.0 * sample_distance);
+
+        let normal_length = (dz_dx * dz_dx + dz_dy * dz_dy + 1.0).sqrt();
+
+        let azimuth = self.azimuth.to_radians();
+        let altitude = self.altitude.to_radians();
+
+        let light = (
+            azimuth.sin() * altitude.cos(),
+            azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        );
+
+        Some(((-dz_dx * light.0 - dz_dy * light.1 + light.2) / normal_length).max(0.0))
+    }
+
+    /// Shades the given color by the illumination, leaving flat terrain as it is.
+    pub fn shade(&self, color: image::Rgba<u8>, illumination: f64) -> image::Rgba<u8> {
+        let flat_illumination = self.altitude.to_radians().sin();
+        let factor = 1.0 + self.opacity * (illumination / flat_illumination - 1.0);
+
+        let shade_channel = |channel: u8| (channel as f64 * factor).round().clamp(0.0, 255.0) as u8;
+
+        image::Rgba([
+            shade_channel(color[0]),
+            shade_channel(color[1]),
+            shade_channel(color[2]),
+            color[3],
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HillshadeSettings;
+    use crate::elevation::{ElevationModel, ElevationTile};
+
+    /// A tile rising towards the east by `gradient` meters per sample (~111 m apart).
+    fn slope(gradient: f32) -> ElevationModel {
+        let samples = (0..11 * 11)
+            .map(|index| (index % 11) as f32 * gradient)
+            .collect();
+
+        ElevationModel::new(vec![ElevationTile::new(
+            0.01, 0.0, 0.001, 0.001, 11, 11, samples,
+        )])
+    }
+
+    #[test]
+    fn test_flat_terrain_keeps_its_color() {
+        let settings = HillshadeSettings::default();
+
+        let illumination = settings
+            .illumination(&slope(0.0), 0.005, 0.005, 100.0)
+            .unwrap();
+
+        assert!((illumination - 45f64.to_radians().sin()).abs() < 1e-9);
+        assert_eq!(
+            settings.shade(image::Rgba([100, 150, 200, 255]), illumination),
+            image::Rgba([100, 150, 200, 255])
+        );
+    }
+
+    #[test]
+    fn test_slopes_facing_the_light_are_brighter() {
+        let settings = HillshadeSettings {
+            azimuth: 270.0,
+            ..Default::default()
+        };
+
+        // rising towards the east means facing west, where the light comes from
+        let towards_light = settings
+            .illumination(&slope(50.0), 0.005, 0.005, 100.0)
+            .unwrap();
+        let away_from_light = settings
+            .illumination(&slope(-50.0), 0.005, 0.005, 100.0)
+            .unwrap();
+
+        assert!(towards_light > 45f64.to_radians().sin());
+        assert!(away_from_light < 45f64.to_radians().sin());
+
+        let color = image::Rgba([100, 100, 100, 255]);
+        assert!(settings.shade(color, towards_light)[0] > 100);
+        assert!(settings.shade(color, away_from_light)[0] < 100);
+
+        // outside of the elevation data
+        assert_eq!(settings.illumination(&slope(50.0), 1.0, 1.0, 100.0), None);
+    }
+}
@@ -0,0 +1,207 @@
+pub mod contours;
+pub mod geotiff;
+pub mod hgt;
+pub mod hillshade;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A regular grid of elevation samples in WGS 84 coordinates, e.g. read from a single DEM file.
+#[derive(Debug, Clone)]
+pub struct ElevationTile {
+    /// latitude of the samples in the first (northernmost) row
+    north: f64,
+    /// longitude of the samples in the first (westernmost) column
+    west: f64,
+    /// distance between two rows in degrees
+    lat_step: f64,
+    /// distance between two columns in degrees
+    lon_step: f64,
+    width: usize,
+    height: usize,
+    /// elevations in meters, row by row from north to south, `NaN` marks voids
+    samples: Vec<f32>,
+}
+
+impl ElevationTile {
+    pub fn new(
+        north: f64,
+        west: f64,
+        lat_step: f64,
+        lon_step: f64,
+        width: usize,
+        height: usize,
+        samples: Vec<f32>,
+    ) -> ElevationTile {
+        assert_eq!(samples.len(), width * height);
+        assert!(width >= 2 && height >= 2);
+
+        ElevationTile {
+            north,
+            west,
+            lat_step,
+            lon_step,
+            width,
+            height,
+            samples,
+        }
+    }
+
+    /// Returns the bilinearly interpolated elevation at the given location, or `None` if the
+    /// location is not covered by this tile or lies next to a void.
+    pub fn elevation_at(&self, lat: f64, lon: f64) -> Option<f64> {
+        let row = (self.north - lat) / self.lat_step;
+        let column = (lon - self.west) / self.lon_step;
+
+        if row < 0.0
+            || column < 0.0
+            || row > (self.height - 1) as f64
+            || column > (self.width - 1) as f64
+        {
+            return None;
+        }
+
+        // make sure that there is always a next row/column to interpolate with
+        let top = (row.floor() as usize).min(self.height - 2);
+        let left = (column.floor() as usize).min(self.width - 2);
+        let row_fraction = row - top as f64;
+        let column_fraction = column - left as f64;
+
+        let elevation = [
+            (top, left, (1.0 - row_fraction) * (1.0 - column_fraction)),
+            (top, left + 1, (1.0 - row_fraction) * column_fraction),
+            (top + 1, left, row_fraction * (1.0 - column_fraction)),
+            (top + 1, left + 1, row_fraction * column_fraction),
+        ]
+        .iter()
+        // samples without any weight must not spread their voids
+        .filter(|(_, _, weight)| *weight > 0.0)
+        .map(|(row, column, weight)| self.samples[row * self.width + column] as f64 * weight)
+        .sum::<f64>();
+
+        if elevation.is_nan() {
+            None
+        } else {
+            Some(elevation)
+        }
+    }
+}
+
+/// Elevation data, possibly combined from multiple DEM files.
+#[derive(Debug, Clone, Default)]
+pub struct ElevationModel {
+    tiles: Vec<ElevationTile>,
+}
+
+impl ElevationModel {
+    pub fn new(tiles: Vec<ElevationTile>) -> ElevationModel {
+        ElevationModel { tiles }
+    }
+
+    /// Loads the given SRTM (`.hgt`) and GeoTIFF (`.tif`, `.tiff`) files.
+    pub fn load<P: AsRef<Path>>(file_paths: &[P]) -> Result<ElevationModel, ElevationError> {
+        let mut tiles = Vec::with_capacity(file_paths.len());
+
+        for file_path in file_paths {
+            let file_path = file_path.as_ref();
+
+            let extension = file_path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_ascii_lowercase());
+
+            let tile = match extension.as_deref() {
+                Some("hgt") => hgt::read_hgt(file_path)?,
+                Some("tif") | Some("tiff") => geotiff::read_geotiff(file_path)?,
+                _ => return Err(ElevationError::UnsupportedFormat(file_path.to_owned())),
+            };
+
+            info!("Loaded elevation data from {}.", file_path.display());
+
+            tiles.push(tile);
+        }
+
+        Ok(ElevationModel { tiles })
+    }
+
+    /// Returns the elevation in meters at the given location, if any of the tiles covers it.
+    pub fn elevation_at(&self, lat: f64, lon: f64) -> Option<f64> {
+        self.tiles
+            .iter()
+            .find_map(|tile| tile.elevation_at(lat, lon))
+    }
+}
+
+#[derive(Debug)]
+pub enum ElevationError {
+    Io(io::Error),
+    Tiff(tiff::TiffError),
+    /// holds the path of the file whose format is not supported
+    UnsupportedFormat(PathBuf),
+    /// holds a description of what is wrong with the file
+    InvalidData(String),
+}
+
+impl Error for ElevationError {}
+
+impl fmt::Display for ElevationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ElevationError::*;
+
+        match self {
+            Io(error) => write!(f, "Could not read elevation data: {}", error),
+            Tiff(error) => write!(f, "Could not decode GeoTIFF: {}", error),
+            UnsupportedFormat(file_path) => write!(
+                f,
+                "Unsupported elevation data format of \"{}\", expected .hgt or .tif.",
+                file_path.display()
+            ),
+            InvalidData(reason) => write!(f, "Invalid elevation data: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for ElevationError {
+    fn from(error: io::Error) -> Self {
+        ElevationError::Io(error)
+    }
+}
+
+impl From<tiff::TiffError> for ElevationError {
+    fn from(error: tiff::TiffError) -> Self {
+        ElevationError::Tiff(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElevationTile;
+
+    #[test]
+    fn test_elevation_at_interpolates_bilinearly() {
+        // 100  200  200
+        // 300  400  NaN
+        let tile = ElevationTile::new(
+            47.0,
+            9.0,
+            1.0,
+            1.0,
+            3,
+            2,
+            vec![100.0, 200.0, 200.0, 300.0, 400.0, f32::NAN],
+        );
+
+        assert_eq!(tile.elevation_at(47.0, 9.0), Some(100.0));
+        assert_eq!(tile.elevation_at(47.0, 9.5), Some(150.0));
+        assert_eq!(tile.elevation_at(46.5, 9.5), Some(250.0));
+        assert_eq!(tile.elevation_at(46.0, 10.0), Some(400.0));
+
+        // next to a void
+        assert_eq!(tile.elevation_at(46.5, 10.5), None);
+        // outside of the tile
+        assert_eq!(tile.elevation_at(47.5, 9.0), None);
+        assert_eq!(tile.elevation_at(47.0, 8.9), None);
+    }
+}
@@ -2,17 +2,15 @@ use crate::data::{NodeData, RelationData, RelationMember, RelationMemberType, Wa
 use std::collections::{HashMap, HashSet};
 use std::num::NonZeroI64;
 
+/// The extracted nodes, ways and relations, each indexed by their ID.
+pub type ExtractedData = (
+    HashMap<NonZeroI64, NodeData>,
+    HashMap<NonZeroI64, WayData>,
+    HashMap<NonZeroI64, RelationData>,
+);
+
 /// Extracts all data available, with a focus on completeness. That is that RelationData only contains elements that also exist in the other datasets.
-pub fn extract_data_from_filepath(
-    file_path: String,
-) -> Result<
-    (
-        HashMap<NonZeroI64, NodeData>,
-        HashMap<NonZeroI64, WayData>,
-        HashMap<NonZeroI64, RelationData>,
-    ),
-    osmpbf::Error,
-> {
+pub fn extract_data_from_filepath(file_path: String) -> Result<ExtractedData, osmpbf::Error> {
     let reader = osmpbf::ElementReader::from_path(file_path)?;
 
     let mut nid_to_node_data: HashMap<NonZeroI64, NodeData> = HashMap::new();
@@ -78,7 +76,7 @@ pub fn extract_data_from_filepath(
                     tags,
                     lat: node.lat(),
                     lon: node.lon(),
-                    way: None,
+                    ways: Vec::new(),
                 },
             );
         } else if let osmpbf::Element::Way(way) = element {
@@ -117,7 +115,10 @@ pub fn extract_data_from_filepath(
     wid_to_way_data.values().for_each(|way_data| {
         way_data.refs().iter().for_each(|nid| {
             if let Some(node_data) = nid_to_node_data.get_mut(nid) {
-                node_data.way = Some(way_data.way_id());
+                // closed ways reference their first node twice
+                if !node_data.ways.contains(&way_data.way_id()) {
+                    node_data.ways.push(way_data.way_id());
+                }
             } else {
                 panic!(
                     "No node found for #{} (belonging to way #{})!",
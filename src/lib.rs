@@ -0,0 +1,16 @@
+extern crate once_cell;
+extern crate osmpbf;
+extern crate pest;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate pest_derive;
+extern crate image;
+
+pub mod data;
+pub mod element;
+pub mod elevation;
+pub mod extractor;
+pub mod mapcss;
+pub mod painter;
+pub mod viewport;
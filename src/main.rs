@@ -1,60 +1,33 @@
-extern crate once_cell;
-extern crate osmpbf;
-extern crate pest;
-#[cfg(windows)]
-extern crate winapi;
-#[macro_use]
-extern crate log;
+extern crate ab_glyph;
 extern crate flexi_logger;
 #[macro_use]
-extern crate pest_derive;
-extern crate image;
-
-mod data;
-mod element;
-mod extractor;
-mod mapcss;
-mod painter;
+extern crate log;
+extern crate rosm;
+#[cfg(windows)]
+extern crate winapi;
 
-use mapcss::declaration::MapCssDeclarationList;
-use painter::Painter;
+use ab_glyph::FontArc;
+use rosm::element::canvas::CanvasElement;
+use rosm::elevation::contours;
+use rosm::elevation::ElevationModel;
+use rosm::extractor;
+use rosm::mapcss;
+use rosm::mapcss::declaration::MapCssDeclarationList;
+use rosm::painter;
+use rosm::viewport::{BoundingBox, OutputSize, ScaleMode};
 use std::error::Error;
-use std::time::Instant;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
 #[cfg(windows)]
 use winapi::{
     shared::minwindef::FALSE,
     um::processthreadsapi::GetCurrentProcess,
     um::psapi::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS},
 };
-const IMAGE_PART_SIZE: u32 = 512;
-
-pub(crate) trait Zero {
-    fn zero() -> Self;
-}
-
-impl Zero for u32 {
-    fn zero() -> u32 {
-        0
-    }
-}
-
-pub(crate) fn round_up_to<T>(int: T, target: T) -> T
-where
-    T: std::cmp::PartialEq
-        + std::ops::Sub<Output = T>
-        + std::ops::Rem<Output = T>
-        + std::ops::Add<Output = T>
-        + Zero
-        + Copy,
-{
-    if int % target == T::zero() {
-        return int;
-    }
 
-    (target - int % target) + int
-}
-
-pub(crate) fn print_peak_memory_usage() {
+fn print_peak_memory_usage() {
     #[cfg(windows)]
     unsafe {
         let mut pmc = std::mem::zeroed::<PROCESS_MEMORY_COUNTERS>();
@@ -71,19 +44,113 @@ pub(crate) fn print_peak_memory_usage() {
     }
 }
 
+/// Prints the problems of the stylesheet, exiting with 1 if there are any.
+fn lint(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let stylesheet = std::fs::read_to_string(file_path)?;
+
+    let diagnostics = match mapcss::lint::lint_mapcss(&stylesheet, Some(file_path)) {
+        Ok(diagnostics) => diagnostics,
+        Err(diagnostic) => vec![diagnostic],
+    };
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}\n", diagnostic);
+    }
+
+    if !diagnostics.is_empty() {
+        eprintln!("{} problems found in {}", diagnostics.len(), file_path);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Rewrites the stylesheet formatted consistently, exiting with 1 if it has syntax errors.
+fn fmt(file_path: &str) -> Result<(), Box<dyn Error>> {
+    let stylesheet = std::fs::read_to_string(file_path)?;
+
+    match mapcss::printer::format_mapcss(&stylesheet) {
+        Ok(formatted) => std::fs::write(file_path, formatted)?,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic.with_file_name(file_path));
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     flexi_logger::Logger::try_with_str("debug")?
         .format(flexi_logger::colored_detailed_format)
         .start()?;
 
+    let mut dem_file_paths: Vec<PathBuf> = Vec::new();
+    let mut output_file_path: Option<PathBuf> = None;
+    let mut font_file_path: Option<PathBuf> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dem" => dem_file_paths.push(
+                args.next()
+                    .ok_or("--dem requires the path of a .hgt or .tif file")?
+                    .into(),
+            ),
+            "--font" => {
+                font_file_path = Some(
+                    args.next()
+                        .ok_or("--font requires the path of a .ttf or .otf file")?
+                        .into(),
+                )
+            }
+            "-o" | "--output" => {
+                output_file_path = Some(
+                    args.next()
+                        .ok_or("-o requires the path of the .png file to write")?
+                        .into(),
+                )
+            }
+            "lint" => {
+                return lint(
+                    &args
+                        .next()
+                        .ok_or("lint requires the path of a .mapcss file")?,
+                )
+            }
+            "fmt" => {
+                return fmt(&args
+                    .next()
+                    .ok_or("fmt requires the path of a .mapcss file")?)
+            }
+            _ => return Err(format!("Unknown argument \"{}\"", arg).into()),
+        }
+    }
+
     print_peak_memory_usage();
 
     let instant = Instant::now();
-    let result = mapcss::parser::MapCssParser::parse_mapcss(include_str!("../include/main.mapcss"));
+    let result = mapcss::parser::MapCssParser::parse_mapcss_from_file(
+        include_str!("../include/main.mapcss"),
+        Some("include/main.mapcss"),
+    );
 
-    let (map_css_acknowledgement, rules) = result.unwrap();
+    let (map_css_acknowledgement, rules, warnings) = match result {
+        Ok(result) => result,
+        Err(diagnostic) => {
+            eprintln!("{}", diagnostic);
+            std::process::exit(1);
+        }
+    };
 
-    dbg!(instant.elapsed());
+    info!(
+        "Parsing the MapCSS stylesheet took {:.2?}.",
+        instant.elapsed()
+    );
+
+    for warning in warnings {
+        eprintln!("{}\n", warning);
+    }
 
     if let Some(map_css_acknowledgement) = map_css_acknowledgement {
         info!(
@@ -98,7 +165,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     info!("Extracting data!");
 
     let instant = Instant::now();
-    let (nid_to_node_data, wid_to_way_data, rid_to_relation_data) =
+    let (mut nid_to_node_data, mut wid_to_way_data, rid_to_relation_data) =
         extractor::extract_data_from_filepath(String::from("liechtenstein-latest.osm.pbf"))?;
 
     print_peak_memory_usage();
@@ -112,16 +179,65 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut painter = painter::PngPainter::default();
 
-    for res in [2_500.0f64] {
+    if let Some(font_file_path) = font_file_path {
+        painter.set_font(FontArc::try_from_vec(std::fs::read(font_file_path)?)?);
+    } else {
+        info!("No font given with --font, labels are left out.");
+    }
+
+    if !dem_file_paths.is_empty() {
+        let elevation_model = ElevationModel::load(&dem_file_paths)?;
+
+        let contour_interval =
+            CanvasElement {}.contour_interval(&MapCssDeclarationList::new(rules.clone()));
+
+        if let (Some(contour_interval), Some(extent)) = (
+            contour_interval,
+            BoundingBox::from_nodes(nid_to_node_data.values()),
+        ) {
+            let instant = Instant::now();
+            let (nodes, ways) =
+                contours::generate_contour_lines(&elevation_model, &extent, contour_interval);
+
+            info!(
+                "Generating {} contour lines every {} m took {:.2?}.",
+                ways.len(),
+                contour_interval,
+                instant.elapsed()
+            );
+
+            nid_to_node_data.extend(nodes.into_iter().map(|node| (node.nid, node)));
+            wid_to_way_data.extend(ways.into_iter().map(|way| (way.way_id(), way)));
+        }
+
+        painter.set_elevation_model(elevation_model);
+    }
+
+    for output_size in [OutputSize::new(2_048, 2_048, ScaleMode::Fit)?] {
         let mapcss_ast = MapCssDeclarationList::new(rules.clone());
 
-        painter.paint(
-            res,
+        let file_path = match &output_file_path {
+            Some(output_file_path) => output_file_path.clone(),
+            None => PathBuf::from(format!(
+                "render-{}-{}x{}.png",
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)?
+                    .as_secs(),
+                output_size.width(),
+                output_size.height()
+            )),
+        };
+
+        painter.paint_to_writer(
+            output_size,
             mapcss_ast,
             nid_to_node_data.clone(),
             wid_to_way_data.clone(),
             rid_to_relation_data.clone(),
-        );
+            BufWriter::new(File::create(&file_path)?),
+        )?;
+
+        info!("Image saved to {}.", file_path.display());
     }
 
     Ok(())
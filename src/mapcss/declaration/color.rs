@@ -175,13 +175,14 @@ static NAMED_CSS_COLORS: Lazy<HashMap<&'static [u8], RGBA>> = Lazy::new(|| {
             red: 0,
             green: 0,
             blue: 0,
-            alpha: 255,
+            alpha: 0,
         },
     );
 
     m
 });
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct RGBA {
     pub red: u8,
@@ -218,85 +219,190 @@ impl From<RGBA> for [f32; 4] {
     }
 }
 
-impl Into<image::Rgba<u8>> for RGBA {
-    fn into(self) -> image::Rgba<u8> {
-        image::Rgba([self.red, self.green, self.blue, self.alpha])
+impl From<RGBA> for image::Rgba<u8> {
+    fn from(color: RGBA) -> image::Rgba<u8> {
+        image::Rgba([color.red, color.green, color.blue, color.alpha])
     }
 }
 
-impl Into<image::Rgb<u8>> for RGBA {
-    fn into(self) -> image::Rgb<u8> {
-        image::Rgb([self.red, self.green, self.blue])
+impl From<RGBA> for image::Rgb<u8> {
+    fn from(color: RGBA) -> image::Rgb<u8> {
+        image::Rgb([color.red, color.green, color.blue])
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ColorParseError {
     InvalidInput,
+    /// holds the name that is not a CSS color name
+    UnknownName(String),
+    /// a component of a functional notation like `rgb()` is out of its range
+    OutOfRange,
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ColorParseError::*;
+
+        match self {
+            InvalidInput => write!(
+                f,
+                "expected #rgb, #rgba, #rrggbb, #rrggbbaa, rgb(), rgba(), hsl(), hsla() or a color name"
+            ),
+            UnknownName(name) => write!(f, "\"{}\" is not a CSS color name", name),
+            OutOfRange => write!(f, "color component out of range"),
+        }
+    }
 }
 
 impl FromStr for RGBA {
     type Err = ColorParseError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let red;
-        let green;
-        let blue;
-        // default to no transparency
-        let mut alpha = 255u8;
-
-        let mut chars = string.chars();
-
-        if chars.next() == Some('#') {
-            // ignore # at the front
-            let color_part_length = string.len() - 1;
-
-            match color_part_length {
-                3 => {
-                    red = u8::from_str_radix(&chars.next().unwrap().to_string(), 16)?;
-                    green = u8::from_str_radix(&chars.next().unwrap().to_string(), 16)?;
-                    blue = u8::from_str_radix(&chars.next().unwrap().to_string(), 16)?;
-                }
-                6 | 8 => {
-                    red = u8::from_str_radix(
-                        &format!("{}{}", chars.next().unwrap(), chars.next().unwrap()),
-                        16,
-                    )?;
-                    green = u8::from_str_radix(
-                        &format!("{}{}", chars.next().unwrap(), chars.next().unwrap()),
-                        16,
-                    )?;
-                    blue = u8::from_str_radix(
-                        &format!("{}{}", chars.next().unwrap(), chars.next().unwrap()),
-                        16,
-                    )?;
-
-                    if color_part_length == 8 {
-                        alpha = u8::from_str_radix(
-                            &format!("{}{}", chars.next().unwrap(), chars.next().unwrap()),
-                            16,
-                        )?;
-                    }
-                }
-                _ => return Err(ColorParseError::InvalidInput),
-            }
-        } else if string.len() <= NAMED_CSS_COLOR_MAX_LENGTH {
+        let string = string.trim();
+
+        if let Some(hex) = string.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+
+        if string.ends_with(')') {
+            return parse_color_function(string);
+        }
+
+        if string.len() <= NAMED_CSS_COLOR_MAX_LENGTH {
             if let Some(&color) = NAMED_CSS_COLORS.get(string.to_ascii_lowercase().as_bytes()) {
                 return Ok(color);
-            } else {
+            }
+        }
+
+        Err(ColorParseError::UnknownName(string.to_owned()))
+    }
+}
+
+/// Parses the hexadecimal notations `rgb`, `rgba`, `rrggbb` and `rrggbbaa`.
+fn parse_hex(hex: &str) -> Result<RGBA, ColorParseError> {
+    if !hex.is_ascii() {
+        return Err(ColorParseError::InvalidInput);
+    }
+
+    let channels = match hex.len() {
+        // every digit is doubled, e.g. #f80 is the same as #ff8800
+        3 | 4 => hex
+            .chars()
+            .map(|digit| u8::from_str_radix(&digit.to_string(), 16).map(|value| value * 17))
+            .collect::<Result<Vec<_>, _>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(ColorParseError::InvalidInput),
+    };
+
+    Ok(RGBA {
+        red: channels[0],
+        green: channels[1],
+        blue: channels[2],
+        // default to no transparency
+        alpha: channels.get(3).copied().unwrap_or(255),
+    })
+}
+
+/// Parses the functional notations `rgb()`, `rgba()`, `hsl()` and `hsla()`, which all accept an
+/// optional alpha component.
+fn parse_color_function(string: &str) -> Result<RGBA, ColorParseError> {
+    let (name, arguments) = string[..string.len() - 1]
+        .split_once('(')
+        .ok_or(ColorParseError::InvalidInput)?;
+
+    let arguments = arguments.split(',').map(str::trim).collect::<Vec<_>>();
+
+    if arguments.len() != 3 && arguments.len() != 4 {
+        return Err(ColorParseError::InvalidInput);
+    }
+
+    let alpha = match arguments.get(3) {
+        Some(alpha) => (parse_fraction(alpha, 1.0)? * 255.0).round() as u8,
+        None => 255,
+    };
+
+    let (red, green, blue) = match name.trim().to_ascii_lowercase().as_str() {
+        "rgb" | "rgba" => (
+            parse_fraction(arguments[0], 255.0)?,
+            parse_fraction(arguments[1], 255.0)?,
+            parse_fraction(arguments[2], 255.0)?,
+        ),
+        "hsl" | "hsla" => {
+            let hue = arguments[0]
+                .strip_suffix("deg")
+                .unwrap_or(arguments[0])
+                .parse::<f64>()
+                .map_err(|_| ColorParseError::InvalidInput)?;
+
+            if !arguments[1].ends_with('%') || !arguments[2].ends_with('%') {
                 return Err(ColorParseError::InvalidInput);
             }
-        } else {
-            return Err(ColorParseError::InvalidInput);
+
+            hsl_to_rgb(
+                hue.rem_euclid(360.0),
+                parse_fraction(arguments[1], 1.0)?,
+                parse_fraction(arguments[2], 1.0)?,
+            )
         }
+        _ => return Err(ColorParseError::InvalidInput),
+    };
 
-        Ok(RGBA {
-            red,
-            green,
-            blue,
-            alpha,
-        })
+    let to_channel = |fraction: f64| (fraction * 255.0).round() as u8;
+
+    Ok(RGBA {
+        red: to_channel(red),
+        green: to_channel(green),
+        blue: to_channel(blue),
+        alpha,
+    })
+}
+
+/// Parses a percentage or a number from 0 to `max` into a fraction from 0 to 1.
+fn parse_fraction(string: &str, max: f64) -> Result<f64, ColorParseError> {
+    let (number, max) = match string.strip_suffix('%') {
+        Some(percentage) => (percentage, 100.0),
+        None => (string, max),
+    };
+
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| ColorParseError::InvalidInput)?;
+
+    if !(0.0..=max).contains(&number) {
+        return Err(ColorParseError::OutOfRange);
     }
+
+    Ok(number / max)
+}
+
+/// Converts a hue in degrees and the saturation and lightness (from 0 to 1) to the red, green
+/// and blue fractions.
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> (f64, f64, f64) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+    let (red, green, blue) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let lightness_offset = lightness - chroma / 2.0;
+
+    (
+        red + lightness_offset,
+        green + lightness_offset,
+        blue + lightness_offset,
+    )
 }
 
 impl From<ParseIntError> for ColorParseError {
@@ -308,7 +414,7 @@ impl From<ParseIntError> for ColorParseError {
 #[cfg(test)]
 mod tests {
 
-    use super::RGBA;
+    use super::{ColorParseError, RGBA};
 
     #[test]
     pub fn test_parse() {
@@ -395,4 +501,72 @@ mod tests {
         assert_eq!("ALICEBLUE".parse::<RGBA>().unwrap(), aliceblue_parsed);
         assert_eq!("alICEblue".parse::<RGBA>().unwrap(), aliceblue_parsed);
     }
+
+    #[test]
+    pub fn test_parse_short_hex() {
+        let rgba = |red, green, blue, alpha| RGBA {
+            red,
+            green,
+            blue,
+            alpha,
+        };
+
+        assert_eq!("#f80".parse::<RGBA>(), Ok(rgba(255, 136, 0, 255)));
+        assert_eq!("#f808".parse::<RGBA>(), Ok(rgba(255, 136, 0, 136)));
+        assert_eq!("transparent".parse::<RGBA>(), Ok(rgba(0, 0, 0, 0)));
+        assert_eq!("#f8".parse::<RGBA>(), Err(ColorParseError::InvalidInput));
+        assert_eq!("#ggg".parse::<RGBA>(), Err(ColorParseError::InvalidInput));
+    }
+
+    #[test]
+    pub fn test_parse_functional_notation() {
+        let rgba = |red, green, blue, alpha| RGBA {
+            red,
+            green,
+            blue,
+            alpha,
+        };
+
+        assert_eq!(
+            "rgb(255, 136, 0)".parse::<RGBA>(),
+            Ok(rgba(255, 136, 0, 255))
+        );
+        assert_eq!(
+            "RGB(100%,0%,50%)".parse::<RGBA>(),
+            Ok(rgba(255, 0, 128, 255))
+        );
+        assert_eq!(
+            "rgba(0, 0, 255, 0.5)".parse::<RGBA>(),
+            Ok(rgba(0, 0, 255, 128))
+        );
+        assert_eq!(
+            "hsl(120, 100%, 25%)".parse::<RGBA>(),
+            Ok(rgba(0, 128, 0, 255))
+        );
+        assert_eq!(
+            "hsl(0deg, 100%, 50%)".parse::<RGBA>(),
+            Ok(rgba(255, 0, 0, 255))
+        );
+        assert_eq!(
+            "hsla(240, 100%, 50%, 20%)".parse::<RGBA>(),
+            Ok(rgba(0, 0, 255, 51))
+        );
+
+        assert_eq!(
+            "rgb(256, 0, 0)".parse::<RGBA>(),
+            Err(ColorParseError::OutOfRange)
+        );
+        assert_eq!(
+            "rgb(0, 0)".parse::<RGBA>(),
+            Err(ColorParseError::InvalidInput)
+        );
+        assert_eq!(
+            "hsl(0, 1, 1)".parse::<RGBA>(),
+            Err(ColorParseError::InvalidInput)
+        );
+        assert_eq!(
+            "notacolor".parse::<RGBA>(),
+            Err(ColorParseError::UnknownName("notacolor".to_owned()))
+        );
+    }
 }
@@ -0,0 +1,67 @@
+use crate::data::{DataSet, ElementData};
+
+/// The state of an element while cascading: the element itself, plus the classes and tags that
+/// were assigned by `set` instructions of the rules matched so far.
+#[derive(Debug)]
+pub struct MatchContext<'a> {
+    element_data: &'a dyn ElementData,
+    /// the other elements, if known, for conditions on the topology of the element
+    data_set: Option<&'a DataSet<'a>>,
+    /// the zoom level rendered at, if known
+    zoom_level: Option<u8>,
+    classes: Vec<String>,
+    /// tags set by the style, taking precedence over the tags of the element
+    set_tags: Vec<(String, String)>,
+}
+
+impl<'a> MatchContext<'a> {
+    pub fn new(
+        element_data: &'a dyn ElementData,
+        data_set: Option<&'a DataSet<'a>>,
+        zoom_level: Option<u8>,
+    ) -> MatchContext<'a> {
+        MatchContext {
+            element_data,
+            data_set,
+            zoom_level,
+            classes: Vec::new(),
+            set_tags: Vec::new(),
+        }
+    }
+
+    pub fn element_data(&self) -> &'a dyn ElementData {
+        self.element_data
+    }
+
+    pub fn data_set(&self) -> Option<&'a DataSet<'a>> {
+        self.data_set
+    }
+
+    pub fn zoom_level(&self) -> Option<u8> {
+        self.zoom_level
+    }
+
+    /// Returns the value of the tag with the given key, as set by the style or the element.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.set_tags
+            .iter()
+            .chain(self.element_data.tags())
+            .find(|(tag_key, _)| tag_key == key)
+            .map(|(_, tag_value)| tag_value.as_str())
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|set_class| set_class == class)
+    }
+
+    pub fn add_class(&mut self, class: &str) {
+        if !self.has_class(class) {
+            self.classes.push(class.to_owned());
+        }
+    }
+
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        self.set_tags.retain(|(tag_key, _)| tag_key != key);
+        self.set_tags.push((key.to_owned(), value.to_owned()));
+    }
+}
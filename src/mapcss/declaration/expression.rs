@@ -0,0 +1,575 @@
+use super::{Length, MapCssDeclarationValueType, MatchContext, RGBA};
+use crate::data::ElementID;
+use crate::mapcss::parser::{quote, FloatSize, IntSize};
+use std::fmt;
+
+/// An `eval()` expression, evaluated for every element the declaration is applied to.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Literal(Value),
+    UnaryOperation(UnaryOperator, Box<Expression>),
+    BinaryOperation(BinaryOperator, Box<Expression>, Box<Expression>),
+    FunctionCall(Function, Vec<Expression>),
+}
+
+/// The dynamically typed result of an expression. Like in JOSM, values are converted to the type
+/// an operator or function expects, which yields `Null` if that is not possible.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Number(FloatSize),
+    String(String),
+}
+
+/// The type of the property the result of an expression is assigned to.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ExpressionType {
+    Boolean,
+    String,
+    Color,
+    Integer,
+    Float,
+    Length,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Function {
+    /// `tag(key)`: the value of the tag with the given key
+    Tag,
+    /// `has_tag_key(key)`: whether the element has a tag with the given key
+    HasTagKey,
+    /// `osm_id()`: the ID of the element
+    OsmId,
+    /// `concat(a, b, …)`: joins all arguments that are not null
+    Concat,
+    /// `any(a, b, …)`: the first argument that is not null
+    Any,
+    /// `cond(condition, then, else)`
+    Cond,
+    Num,
+    Str,
+    Int,
+    Boolean,
+    Max,
+    Min,
+    Abs,
+    Sqrt,
+    Round,
+    Ceil,
+    Floor,
+    Upper,
+    Lower,
+    Trim,
+    Length,
+    Equal,
+    Replace,
+    StartsWith,
+    EndsWith,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        use Function::*;
+
+        Some(match name {
+            "tag" => Tag,
+            "has_tag_key" => HasTagKey,
+            "osm_id" => OsmId,
+            "concat" => Concat,
+            "any" => Any,
+            "cond" => Cond,
+            "num" => Num,
+            "str" => Str,
+            "int" => Int,
+            "boolean" => Boolean,
+            "max" => Max,
+            "min" => Min,
+            "abs" => Abs,
+            "sqrt" => Sqrt,
+            "round" => Round,
+            "ceil" => Ceil,
+            "floor" => Floor,
+            "upper" => Upper,
+            "lower" => Lower,
+            "trim" => Trim,
+            "length" => Length,
+            "equal" => Equal,
+            "replace" => Replace,
+            "startswith" => StartsWith,
+            "endswith" => EndsWith,
+
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        use Function::*;
+
+        match self {
+            Tag => "tag",
+            HasTagKey => "has_tag_key",
+            OsmId => "osm_id",
+            Concat => "concat",
+            Any => "any",
+            Cond => "cond",
+            Num => "num",
+            Str => "str",
+            Int => "int",
+            Boolean => "boolean",
+            Max => "max",
+            Min => "min",
+            Abs => "abs",
+            Sqrt => "sqrt",
+            Round => "round",
+            Ceil => "ceil",
+            Floor => "floor",
+            Upper => "upper",
+            Lower => "lower",
+            Trim => "trim",
+            Length => "length",
+            Equal => "equal",
+            Replace => "replace",
+            StartsWith => "startswith",
+            EndsWith => "endswith",
+        }
+    }
+
+    /// Returns whether the function can be called with the given number of arguments.
+    pub fn accepts_argument_count(&self, argument_count: usize) -> bool {
+        use Function::*;
+
+        match self {
+            OsmId => argument_count == 0,
+            Tag | HasTagKey | Num | Str | Int | Boolean | Abs | Sqrt | Round | Ceil | Floor
+            | Upper | Lower | Trim | Length => argument_count == 1,
+            Equal | StartsWith | EndsWith => argument_count == 2,
+            Cond | Replace => argument_count == 3,
+            Concat | Any => true,
+            Max | Min => argument_count >= 1,
+        }
+    }
+}
+
+impl Value {
+    pub fn as_number(&self) -> Option<FloatSize> {
+        match self {
+            Value::Null => None,
+            Value::Boolean(boolean) => Some(if *boolean { 1.0 } else { 0.0 }),
+            Value::Number(number) => Some(*number),
+            Value::String(string) => string.trim().parse::<FloatSize>().ok(),
+        }
+    }
+
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            Value::Null => None,
+            value => Some(value.to_string()),
+        }
+    }
+
+    /// Follows the MapCSS notion of truthiness, where e.g. `no` and `0` are false.
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Boolean(boolean) => *boolean,
+            Value::Number(number) => *number != 0.0 && !number.is_nan(),
+            Value::String(string) => !matches!(string.as_str(), "" | "no" | "false" | "0"),
+        }
+    }
+
+    /// Converts the value into the type of a property, returning `None` if it is null or not
+    /// convertible, which leaves the property unset.
+    pub fn coerce(&self, expression_type: ExpressionType) -> Option<MapCssDeclarationValueType> {
+        Some(match expression_type {
+            ExpressionType::Boolean => match self {
+                Value::Null => return None,
+                value => MapCssDeclarationValueType::Boolean(value.as_bool()),
+            },
+            ExpressionType::String => MapCssDeclarationValueType::String(self.as_string()?),
+            ExpressionType::Color => {
+                MapCssDeclarationValueType::Color(self.as_string()?.parse::<RGBA>().ok()?)
+            }
+            ExpressionType::Integer => {
+                MapCssDeclarationValueType::Integer(self.as_number()? as IntSize)
+            }
+            ExpressionType::Float => MapCssDeclarationValueType::Float(self.as_number()?),
+            // unitless numbers are pixels, while strings may carry a unit like "3m"
+            ExpressionType::Length => MapCssDeclarationValueType::Length(match self {
+                Value::String(string) => string.parse::<Length>().ok()?,
+                value => Length::pixels(value.as_number()?),
+            }),
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(string) => write!(f, "{}", string),
+        }
+    }
+}
+
+impl Expression {
+    pub fn evaluate(&self, context: &MatchContext<'_>) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::UnaryOperation(operator, operand) => {
+                let operand = operand.evaluate(context);
+
+                match operator {
+                    UnaryOperator::Negate => number(operand.as_number().map(|number| -number)),
+                    UnaryOperator::Not => Value::Boolean(!operand.as_bool()),
+                }
+            }
+            Expression::BinaryOperation(operator, left, right) => {
+                evaluate_binary_operation(*operator, left, right, context)
+            }
+            Expression::FunctionCall(function, arguments) => {
+                evaluate_function_call(*function, arguments, context)
+            }
+        }
+    }
+}
+
+/// Wraps a number, turning results like infinity (e.g. after a division by zero) into null.
+fn number(number: Option<FloatSize>) -> Value {
+    match number {
+        Some(number) if number.is_finite() => Value::Number(number),
+        _ => Value::Null,
+    }
+}
+
+fn evaluate_binary_operation(
+    operator: BinaryOperator,
+    left: &Expression,
+    right: &Expression,
+    context: &MatchContext<'_>,
+) -> Value {
+    use BinaryOperator::*;
+
+    let left = left.evaluate(context);
+
+    // short-circuit like any other language does
+    match operator {
+        Or if left.as_bool() => return Value::Boolean(true),
+        And if !left.as_bool() => return Value::Boolean(false),
+        Or | And => return Value::Boolean(right.evaluate(context).as_bool()),
+        _ => (),
+    }
+
+    let right = right.evaluate(context);
+
+    let arithmetic = |operation: fn(FloatSize, FloatSize) -> FloatSize| {
+        number(
+            left.as_number()
+                .zip(right.as_number())
+                .map(|(left, right)| operation(left, right)),
+        )
+    };
+
+    let comparison =
+        |operation: fn(&FloatSize, &FloatSize) -> bool| match (left.as_number(), right.as_number())
+        {
+            (Some(left), Some(right)) => Value::Boolean(operation(&left, &right)),
+            _ => Value::Null,
+        };
+
+    match operator {
+        Equal => Value::Boolean(values_equal(&left, &right)),
+        NotEqual => Value::Boolean(!values_equal(&left, &right)),
+        LessEqual => comparison(FloatSize::le),
+        GreaterEqual => comparison(FloatSize::ge),
+        Less => comparison(FloatSize::lt),
+        Greater => comparison(FloatSize::gt),
+        Add => arithmetic(|left, right| left + right),
+        Subtract => arithmetic(|left, right| left - right),
+        Multiply => arithmetic(|left, right| left * right),
+        Divide => arithmetic(|left, right| left / right),
+        Modulo => arithmetic(|left, right| left % right),
+        Or | And => unreachable!(),
+    }
+}
+
+/// Compares numerically if both values are numbers (so that `"3.0" == 3`), and textually
+/// otherwise.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left.as_number(), right.as_number()) {
+        (Some(left), Some(right)) => left == right,
+        _ => left.as_string() == right.as_string(),
+    }
+}
+
+fn evaluate_function_call(
+    function: Function,
+    arguments: &[Expression],
+    context: &MatchContext<'_>,
+) -> Value {
+    use Function::*;
+
+    // evaluates the nth argument, the number of arguments is validated when parsing
+    let argument = |index: usize| arguments[index].evaluate(context);
+
+    let string_function = |operation: fn(&str) -> String| match argument(0).as_string() {
+        Some(string) => Value::String(operation(&string)),
+        None => Value::Null,
+    };
+
+    let number_function =
+        |operation: fn(FloatSize) -> FloatSize| number(argument(0).as_number().map(operation));
+
+    match function {
+        Tag => match argument(0).as_string() {
+            Some(key) => context
+                .tag(&key)
+                .map(|tag_value| Value::String(tag_value.to_owned()))
+                .unwrap_or(Value::Null),
+            None => Value::Null,
+        },
+        HasTagKey => Value::Boolean(match argument(0).as_string() {
+            Some(key) => context.tag(&key).is_some(),
+            None => false,
+        }),
+        OsmId => match context.element_data().id() {
+            ElementID::Node(id) | ElementID::Way(id) | ElementID::Relation(id) => {
+                Value::Number(id.get() as FloatSize)
+            }
+            ElementID::Canvas => Value::Null,
+        },
+        Concat => Value::String(
+            arguments
+                .iter()
+                .filter_map(|argument| argument.evaluate(context).as_string())
+                .collect(),
+        ),
+        Any => arguments
+            .iter()
+            .map(|argument| argument.evaluate(context))
+            .find(|value| *value != Value::Null)
+            .unwrap_or(Value::Null),
+        Cond => {
+            if argument(0).as_bool() {
+                argument(1)
+            } else {
+                argument(2)
+            }
+        }
+        Num => number(argument(0).as_number()),
+        Str => argument(0)
+            .as_string()
+            .map(Value::String)
+            .unwrap_or(Value::Null),
+        Int => number_function(FloatSize::trunc),
+        Boolean => Value::Boolean(argument(0).as_bool()),
+        Max | Min => {
+            let numbers = arguments
+                .iter()
+                .filter_map(|argument| argument.evaluate(context).as_number());
+
+            number(if function == Max {
+                numbers.reduce(FloatSize::max)
+            } else {
+                numbers.reduce(FloatSize::min)
+            })
+        }
+        Abs => number_function(FloatSize::abs),
+        Sqrt => number_function(FloatSize::sqrt),
+        Round => number_function(FloatSize::round),
+        Ceil => number_function(FloatSize::ceil),
+        Floor => number_function(FloatSize::floor),
+        Upper => string_function(str::to_uppercase),
+        Lower => string_function(str::to_lowercase),
+        Trim => string_function(|string| string.trim().to_owned()),
+        Length => match argument(0).as_string() {
+            Some(string) => Value::Number(string.chars().count() as FloatSize),
+            None => Value::Null,
+        },
+        Equal => Value::Boolean(values_equal(&argument(0), &argument(1))),
+        Replace => match (
+            argument(0).as_string(),
+            argument(1).as_string(),
+            argument(2).as_string(),
+        ) {
+            (Some(string), Some(from), Some(to)) => Value::String(string.replace(&from, &to)),
+            _ => Value::Null,
+        },
+        StartsWith | EndsWith => match (argument(0).as_string(), argument(1).as_string()) {
+            (Some(string), Some(affix)) => Value::Boolean(if function == StartsWith {
+                string.starts_with(&affix)
+            } else {
+                string.ends_with(&affix)
+            }),
+            _ => Value::Null,
+        },
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Literal(Value::String(string)) => write!(f, "{}", quote(string)),
+            Expression::Literal(value) => write!(f, "{}", value),
+            Expression::UnaryOperation(operator, operand) => write!(
+                f,
+                "{}{}",
+                match operator {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "!",
+                },
+                operand
+            ),
+            Expression::BinaryOperation(operator, left, right) => {
+                write!(f, "({} {} {})", left, operator, right)
+            }
+            Expression::FunctionCall(function, arguments) => {
+                write!(f, "{}(", function.name())?;
+
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", argument)?;
+                }
+
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BinaryOperator::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Or => "||",
+                And => "&&",
+                Equal => "==",
+                NotEqual => "!=",
+                LessEqual => "<=",
+                GreaterEqual => ">=",
+                Less => "<",
+                Greater => ">",
+                Add => "+",
+                Subtract => "-",
+                Multiply => "*",
+                Divide => "/",
+                Modulo => "%",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapcss::declaration::tests::{cascade, text, width};
+    use crate::mapcss::declaration::{MapCssDeclarationProperty, MapCssDeclarationValueType, RGBA};
+    use crate::mapcss::parser::MapCssParser;
+
+    #[test]
+    fn test_expressions_are_evaluated_per_element() {
+        let lookup = |tags: &[(&str, &str)], property| {
+            cascade(
+                r##"way {
+                    width: eval(1 + tag("lanes") * 1.5);
+                    text: eval(concat(tag("name"), " (", any(tag("ele"), "?"), ")"));
+                    color: eval(cond(tag("oneway") == "yes" && !has_tag_key("bridge"), "red", "#00ff00"));
+                    z-index: eval(-(2 + 3) * 2 % 7);
+                }"##,
+                tags,
+                property,
+            )
+        };
+
+        let tags = [("lanes", "2"), ("name", "Landstrasse"), ("oneway", "yes")];
+
+        assert_eq!(lookup(&tags, MapCssDeclarationProperty::Width), width(4.0));
+        assert_eq!(
+            lookup(&tags, MapCssDeclarationProperty::Text),
+            text("Landstrasse (?)")
+        );
+        assert_eq!(
+            lookup(&tags, MapCssDeclarationProperty::Color),
+            Some(MapCssDeclarationValueType::Color(
+                "red".parse::<RGBA>().unwrap()
+            ))
+        );
+        assert_eq!(
+            lookup(&tags, MapCssDeclarationProperty::ZIndex),
+            Some(MapCssDeclarationValueType::Float(-3.0))
+        );
+
+        // a missing tag makes the arithmetic null, leaving the width unset
+        assert_eq!(lookup(&[], MapCssDeclarationProperty::Width), None);
+        assert_eq!(
+            lookup(&[("bridge", "yes")], MapCssDeclarationProperty::Color),
+            Some(MapCssDeclarationValueType::Color(
+                "#00ff00".parse::<RGBA>().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_comments_in_expressions_are_ignored() {
+        let lookup = |property| {
+            cascade(
+                r#"way {
+                    width: eval(/* lanes */ 1 /* c */ + 2);
+                    text: eval(concat( /* name */ tag("name"), /* c */ "!") /* c */);
+                }"#,
+                &[("name", "Landstrasse")],
+                property,
+            )
+        };
+
+        assert_eq!(lookup(MapCssDeclarationProperty::Width), width(3.0));
+        assert_eq!(
+            lookup(MapCssDeclarationProperty::Text),
+            text("Landstrasse!")
+        );
+    }
+
+    #[test]
+    fn test_unknown_functions_are_rejected() {
+        let (_, rules, warnings) =
+            MapCssParser::parse_mapcss("way { width: eval(unknown(1)); text: eval(tag()); }")
+                .unwrap();
+
+        assert_eq!(warnings.len(), 2);
+        assert!(rules
+            .values()
+            .flatten()
+            .all(|rule| rule.declarations.is_empty()));
+    }
+}
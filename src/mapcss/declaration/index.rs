@@ -0,0 +1,105 @@
+use super::{MapCssDeclarationProperty, MapCssDeclarationValueType, MapCssRule};
+use std::collections::{HashMap, HashSet};
+
+/// The rules of a selector type by the key of a tag they require, so that only the rules
+/// possibly matching an element need to be checked.
+#[derive(Debug, Clone, Default)]
+pub struct RuleIndex {
+    /// positions of the rules requiring a tag with the key
+    by_tag_key: HashMap<String, Vec<usize>>,
+    /// positions of the rules that need to be checked for every element
+    unconditional: Vec<usize>,
+}
+
+impl RuleIndex {
+    /// Indexes the rules, where rules requiring a tag that is assigned by a `set` instruction
+    /// are checked for every element, as the element itself may lack the tag.
+    pub fn new(rules: &[MapCssRule], set_tag_keys: &HashSet<&str>) -> RuleIndex {
+        let mut rule_index = RuleIndex::default();
+
+        for (position, rule) in rules.iter().enumerate() {
+            match rule.conditions.required_tag_key() {
+                Some(key) if !set_tag_keys.contains(key) => rule_index
+                    .by_tag_key
+                    .entry(key.to_owned())
+                    .or_default()
+                    .push(position),
+                _ => rule_index.unconditional.push(position),
+            }
+        }
+
+        rule_index
+    }
+
+    /// Returns the positions of the rules that may match an element with the given tags,
+    /// unordered.
+    pub fn candidates<'a>(
+        &'a self,
+        tags: &'a [(String, String)],
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.unconditional.iter().copied().chain(
+            tags.iter()
+                .filter_map(move |(key, _)| self.by_tag_key.get(key))
+                .flatten()
+                .copied(),
+        )
+    }
+}
+
+/// Returns the keys of all tags assigned by `set key=value;` in the rules.
+pub fn set_tag_keys<'a>(rules: impl Iterator<Item = &'a MapCssRule>) -> HashSet<&'a str> {
+    rules
+        .flat_map(|rule| &rule.declarations)
+        .filter_map(|declaration| match declaration {
+            (MapCssDeclarationProperty::SetTag, MapCssDeclarationValueType::Tag(key, _)) => {
+                Some(key.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_tag_keys, RuleIndex};
+    use crate::mapcss::parser::MapCssParser;
+    use crate::mapcss::selectors::SelectorType;
+
+    #[test]
+    fn test_index_rules_by_tag_key() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            way { width: 1; }
+            way[highway=primary] { width: 2; }
+            way|z12-[!bridge][highway?] { width: 3; }
+            way[railway] { width: 4; }
+            way[railway!=rail] { width: 5; }
+            way[is_road] { width: 6; }
+            node[amenity] { set is_road=no; }
+            "#,
+        )
+        .unwrap();
+
+        let rule_index = RuleIndex::new(
+            &rules[&SelectorType::Way],
+            &set_tag_keys(rules.values().flatten()),
+        );
+
+        let candidates = |tags: &[(&str, &str)]| {
+            let tags: Vec<_> = tags
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let mut candidates: Vec<_> = rule_index.candidates(&tags).collect();
+            candidates.sort_unstable();
+            candidates
+        };
+
+        assert_eq!(candidates(&[]), [0, 4, 5]);
+        assert_eq!(candidates(&[("highway", "primary")]), [0, 1, 2, 4, 5]);
+        assert_eq!(
+            candidates(&[("railway", "rail"), ("name", "Vaduz")]),
+            [0, 3, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::mapcss::parser::FloatSize;
+use crate::mapcss::MapCssParseError;
+use std::fmt;
+use std::str::FromStr;
+
+/// CSS pixels per point, as a point is 1/72 and a pixel 1/96 of an inch.
+const PIXELS_PER_POINT: FloatSize = 96.0 / 72.0;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Length {
+    pub value: FloatSize,
+    pub unit: LengthUnit,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum LengthUnit {
+    /// screen pixels, the default for unitless numbers
+    #[default]
+    Pixels,
+    /// typographic points, e.g. for print
+    Points,
+    /// meters on the ground, which scale with the zoom level and the projection
+    Meters,
+}
+
+impl Length {
+    pub fn pixels(value: FloatSize) -> Length {
+        Length {
+            value,
+            unit: LengthUnit::Pixels,
+        }
+    }
+
+    /// Resolves the length to pixels, where ground lengths need to know how many meters one
+    /// pixel covers at the location they are drawn at.
+    pub fn to_pixels(&self, meters_per_pixel: FloatSize) -> FloatSize {
+        match self.unit {
+            LengthUnit::Pixels => self.value,
+            LengthUnit::Points => self.value * PIXELS_PER_POINT,
+            LengthUnit::Meters => self.value / meters_per_pixel,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = MapCssParseError;
+
+    /// Parses a number followed by an optional unit, e.g. `2`, `1.5px`, `8pt` or `12m`.
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let string = string.trim();
+        let unit_start = string
+            .find(|char: char| char.is_ascii_alphabetic())
+            .unwrap_or(string.len());
+
+        let value = string[..unit_start]
+            .trim()
+            .parse::<FloatSize>()
+            .map_err(|_| MapCssParseError::InvalidUnit("a number"))?;
+
+        let unit = match &string[unit_start..] {
+            "" | "px" => LengthUnit::Pixels,
+            "pt" => LengthUnit::Points,
+            "m" => LengthUnit::Meters,
+            _ => return Err(MapCssParseError::InvalidUnit("px, pt or m")),
+        };
+
+        Ok(Length { value, unit })
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit)
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LengthUnit::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Pixels => "px",
+                Points => "pt",
+                Meters => "m",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Length, LengthUnit};
+
+    #[test]
+    fn test_parse_length() {
+        assert_eq!("2".parse::<Length>().unwrap(), Length::pixels(2.0));
+        assert_eq!("0.5px".parse::<Length>().unwrap(), Length::pixels(0.5));
+        assert_eq!(
+            "12m".parse::<Length>().unwrap(),
+            Length {
+                value: 12.0,
+                unit: LengthUnit::Meters
+            }
+        );
+        assert!("5em".parse::<Length>().is_err());
+        assert!("px".parse::<Length>().is_err());
+    }
+
+    #[test]
+    fn test_to_pixels() {
+        assert_eq!(Length::pixels(3.0).to_pixels(10.0), 3.0);
+        assert_eq!("9pt".parse::<Length>().unwrap().to_pixels(10.0), 12.0);
+        // 12 m at 2 m per pixel
+        assert_eq!("12m".parse::<Length>().unwrap().to_pixels(2.0), 6.0);
+    }
+}
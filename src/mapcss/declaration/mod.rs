@@ -1,16 +1,40 @@
-use crate::data::{ElementData, ElementID};
+use crate::data::{DataSet, ElementData, ElementID};
 use crate::mapcss::parser::{FloatSize, IntSize};
-use crate::mapcss::selectors::{SelectorCondition, SelectorType};
-use std::collections::HashMap;
+use crate::mapcss::selectors::{
+    is_right_hand_traffic, PseudoClass, Selector, SelectorCondition, SelectorType, TagNumber,
+    ALL_LAYERS, DEFAULT_LAYER,
+};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
-use std::sync::atomic::{AtomicBool, Ordering};
 
 mod color;
+mod context;
+mod expression;
+mod index;
+mod length;
 
-pub use color::RGBA;
+pub use color::{ColorParseError, RGBA};
+pub use context::MatchContext;
+pub use expression::{BinaryOperator, Expression, ExpressionType, Function, UnaryOperator, Value};
+pub use length::{Length, LengthUnit};
+
+use index::{set_tag_keys, RuleIndex};
 
 pub type MapCssDeclaration = (MapCssDeclarationProperty, MapCssDeclarationValueType);
 
+/// All parsed rules, grouped by the selector type and ordered like in the stylesheet.
+pub type MapCssRules = HashMap<SelectorType, Vec<MapCssRule>>;
+
+/// The declarations of a rule for one of its selectors, e.g. `way[highway] { width: 2; }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapCssRule {
+    /// the position of the selector in the stylesheet, later rules override earlier ones
+    pub index: usize,
+    pub conditions: SelectorCondition,
+    pub declarations: Vec<MapCssDeclaration>,
+}
+
 pub trait ToIntegerValue {
     fn to_integer(&self) -> IntSize;
 }
@@ -19,6 +43,10 @@ pub trait ToFloatValue {
     fn to_float(&self) -> FloatSize;
 }
 
+pub trait ToLengthValue {
+    fn to_length(&self) -> Length;
+}
+
 pub trait ToColorValue {
     fn to_color(&self) -> RGBA;
 }
@@ -27,30 +55,71 @@ pub trait ToBooleanValue {
     fn to_bool(&self) -> bool;
 }
 
-static DID_BLAME_ZOOM_LEVEL_NOT_SUPPORTED: AtomicBool = AtomicBool::new(false);
-static DID_BLAME_HAS_PSEUDO_CLASS_NOT_SUPPORTED: AtomicBool = AtomicBool::new(false);
-static DID_BLAME_HAS_DESCENDANT_NOT_SUPPORTED: AtomicBool = AtomicBool::new(false);
-
 #[derive(Debug, Clone)]
 pub struct MapCssDeclarationList {
-    declarations: HashMap<SelectorType, HashMap<SelectorCondition, Vec<MapCssDeclaration>>>,
+    declarations: MapCssRules,
+    /// the rules of each selector type indexed by the tags they require
+    rule_indices: HashMap<SelectorType, RuleIndex>,
+    /// the zoom level rendered at, zoom conditions always match while it is unknown
+    zoom_level: Option<u8>,
 }
 
 // TODO: Add merge(MapCssDeclarationList) method merging the current list with the latter (latter wins) => cascading properties
 // being used in the rendering process
 impl MapCssDeclarationList {
-    pub fn new(
-        declarations: HashMap<SelectorType, HashMap<SelectorCondition, Vec<MapCssDeclaration>>>,
-    ) -> MapCssDeclarationList {
-        MapCssDeclarationList { declarations }
+    pub fn new(declarations: MapCssRules) -> MapCssDeclarationList {
+        let set_tag_keys = set_tag_keys(declarations.values().flatten());
+        let rule_indices = declarations
+            .iter()
+            .map(|(selector_type, rules)| (*selector_type, RuleIndex::new(rules, &set_tag_keys)))
+            .collect();
+
+        MapCssDeclarationList {
+            declarations,
+            rule_indices,
+            zoom_level: None,
+        }
+    }
+
+    /// Sets the zoom level that conditions like `|z12-` or `@media (min-zoom: 12)` are
+    /// checked against. Fractional zoom levels are rounded down.
+    pub fn set_zoom_level(&mut self, zoom_level: FloatSize) {
+        self.zoom_level = Some(zoom_level.floor().clamp(0.0, u8::MAX as FloatSize) as u8);
+    }
+
+    /// Returns the names of all layers the rules draw to, starting with the default layer.
+    /// Each layer is an own style instance of an element, e.g. for `way::casing`.
+    pub fn layers(&self) -> Vec<&str> {
+        let named_layers: BTreeSet<&str> = self
+            .declarations
+            .values()
+            .flatten()
+            .map(|rule| rule.conditions.layer())
+            .filter(|layer| *layer != DEFAULT_LAYER && *layer != ALL_LAYERS)
+            .collect();
+
+        std::iter::once(DEFAULT_LAYER).chain(named_layers).collect()
     }
 
     pub fn search_cascading(
         &self,
         element_data: Box<dyn ElementData>,
         declaration_property_name: &MapCssDeclarationProperty,
-    ) -> Option<&MapCssDeclarationValueType> {
-        // needs to be ordered from the less specific (SelectorType::Any) to the most specific one
+    ) -> Option<Cow<'_, MapCssDeclarationValueType>> {
+        self.search_cascading_in_layer(element_data, None, DEFAULT_LAYER, declaration_property_name)
+    }
+
+    /// Cascades the rules of the given layer, plus the ones of `::*` which apply to all layers,
+    /// in the order of the stylesheet, so that the last matching declaration wins.
+    /// Conditions on the topology of the element (like `:connection`) only match if the other
+    /// elements are given by the data set.
+    pub fn search_cascading_in_layer(
+        &self,
+        element_data: Box<dyn ElementData>,
+        data_set: Option<&DataSet<'_>>,
+        layer: &str,
+        declaration_property_name: &MapCssDeclarationProperty,
+    ) -> Option<Cow<'_, MapCssDeclarationValueType>> {
         let selectors: &[SelectorType] = match element_data.id() {
             ElementID::Canvas => &[SelectorType::Any, SelectorType::Canvas],
             ElementID::Node(_) => &[SelectorType::Any, SelectorType::Node],
@@ -61,21 +130,68 @@ impl MapCssDeclarationList {
             },
         };
 
-        let mut last_seen_declaration_value_type: Option<&MapCssDeclarationValueType> = None;
+        let mut context = MatchContext::new(element_data.as_ref(), data_set, self.zoom_level);
+        let mut last_seen_declaration_value_type: Option<Cow<'_, MapCssDeclarationValueType>> =
+            None;
 
+        // only the rules that may match the tags of the element are checked
+        let mut candidate_rules = Vec::new();
         for selector in selectors.iter() {
-            let Some(declaration_list) = self.declarations.get(selector) else { continue; };
+            let (Some(rules), Some(rule_index)) = (
+                self.declarations.get(selector),
+                self.rule_indices.get(selector),
+            ) else {
+                continue;
+            };
+
+            candidate_rules.extend(
+                rule_index
+                    .candidates(element_data.tags())
+                    .map(|position| &rules[position]),
+            );
+        }
+
+        // the rules of all selector types are cascaded in the order of the stylesheet
+        candidate_rules.sort_unstable_by_key(|rule| rule.index);
+
+        for rule in candidate_rules {
+            let rule_layer = rule.conditions.layer();
+            if rule_layer != layer && rule_layer != ALL_LAYERS {
+                continue;
+            }
+
+            if !check_conditions(&context, &rule.conditions) {
+                continue;
+            }
+
+            // selector matches to our element, search for declarations that set our target property
+            for (set_declaration_name, set_declaration_value) in &rule.declarations {
+                // classes and tags are set in cascade order, so that only the following rules see them
+                match (set_declaration_name, set_declaration_value) {
+                    (
+                        MapCssDeclarationProperty::SetClass,
+                        MapCssDeclarationValueType::String(class),
+                    ) => context.add_class(class),
+                    (
+                        MapCssDeclarationProperty::SetTag,
+                        MapCssDeclarationValueType::Tag(key, value),
+                    ) => context.set_tag(key, value),
+                    _ => (),
+                }
 
-            for (selector_condition, declaration_property_to_value) in declaration_list {
-                if !check_conditions(&element_data, selector_condition) {
+                if set_declaration_name != declaration_property_name {
                     continue;
                 }
 
-                // selector matches to our element, search for declarations that set our target property
-                for (set_declaration_name, set_declaration_value) in declaration_property_to_value {
-                    if set_declaration_name == declaration_property_name {
-                        last_seen_declaration_value_type = Some(set_declaration_value);
+                if let MapCssDeclarationValueType::Eval(expression, expression_type) =
+                    set_declaration_value
+                {
+                    // an expression evaluating to null does not set the property
+                    if let Some(value) = expression.evaluate(&context).coerce(*expression_type) {
+                        last_seen_declaration_value_type = Some(Cow::Owned(value));
                     }
+                } else {
+                    last_seen_declaration_value_type = Some(Cow::Borrowed(set_declaration_value));
                 }
             }
         }
@@ -87,7 +203,7 @@ impl MapCssDeclarationList {
         &self,
         element_data: Box<dyn ElementData>,
         declaration_property_name: &MapCssDeclarationProperty,
-    ) -> &MapCssDeclarationValueType {
+    ) -> Cow<'_, MapCssDeclarationValueType> {
         match self.search_cascading(element_data, declaration_property_name) {
             Some(value) => value,
             None => panic!(
@@ -102,10 +218,10 @@ impl MapCssDeclarationList {
         element_data: Box<dyn ElementData>,
         declaration_property_name: &MapCssDeclarationProperty,
         default: &'a MapCssDeclarationValueType,
-    ) -> &'a MapCssDeclarationValueType {
+    ) -> Cow<'a, MapCssDeclarationValueType> {
         match self.search_cascading(element_data, declaration_property_name) {
             Some(value) => value,
-            None => default,
+            None => Cow::Borrowed(default),
         }
     }
 }
@@ -128,9 +244,13 @@ pub enum MapCssDeclarationProperty {
     Text,
     TextColor,
     TextPosition,
+    TextAnchorHorizontal,
+    TextAnchorVertical,
+    TextOffsetY,
     TextHaloColor,
     TextHaloRadius,
     TextWrapWidth,
+    TextWrapCharacter,
     TextSpacing,
 
     Color,
@@ -148,6 +268,66 @@ pub enum MapCssDeclarationProperty {
     Width,
 
     ZIndex,
+
+    // set .class; and set tag=value;
+    SetClass,
+    SetTag,
+
+    // canvas {}
+    HillshadeOpacity,
+    HillshadeAzimuth,
+    HillshadeAltitude,
+    ContourInterval,
+}
+
+/// Writes the name of the property as used in the stylesheet, e.g. `fill-color`.
+impl fmt::Display for MapCssDeclarationProperty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MapCssDeclarationProperty::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Title => "title",
+                Version => "version",
+                Description => "description",
+                Acknowledgement => "acknowledgement",
+                Linecap => "linecap",
+                Linejoin => "linejoin",
+                AllowOverlap => "allow_overlap",
+                Dashes => "dashes",
+                Text => "text",
+                TextColor => "text-color",
+                TextPosition => "text-position",
+                TextAnchorHorizontal => "text-anchor-horizontal",
+                TextAnchorVertical => "text-anchor-vertical",
+                TextOffsetY => "text-offset-y",
+                TextHaloColor => "text-halo-color",
+                TextHaloRadius => "text-halo-radius",
+                TextWrapWidth => "text-wrap-width",
+                TextWrapCharacter => "text-wrap-character",
+                TextSpacing => "text-spacing",
+                Color => "color",
+                FontSize => "font-size",
+                FontColor => "font-color",
+                FontFamily => "font-family",
+                IconImage => "icon-image",
+                PatternImage => "pattern-image",
+                Opacity => "opacity",
+                FillOpacity => "fill-opacity",
+                FillColor => "fill-color",
+                FillImage => "fill-image",
+                Width => "width",
+                ZIndex => "z-index",
+                SetClass | SetTag => "set",
+                HillshadeOpacity => "hillshade-opacity",
+                HillshadeAzimuth => "hillshade-azimuth",
+                HillshadeAltitude => "hillshade-altitude",
+                ContourInterval => "contour-interval",
+            }
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -158,9 +338,16 @@ pub enum MapCssDeclarationValueType {
     LinecapDeclarationVariant(LinecapDeclarationVariant),
     LinejoinDeclarationVariant(LinejoinDeclarationVariant),
     TextPositionDeclarationVariant(TextPositionDeclarationVariant),
+    TextAnchorHorizontalDeclarationVariant(TextAnchorHorizontalDeclarationVariant),
+    TextAnchorVerticalDeclarationVariant(TextAnchorVerticalDeclarationVariant),
     IntegerArray(Vec<IntSize>),
     Integer(IntSize),
     Float(FloatSize),
+    Length(Length),
+    /// a tag assigned by `set key=value;`
+    Tag(String, String),
+    /// an `eval()` expression whose result is converted to the given type
+    Eval(Expression, ExpressionType),
 }
 
 impl fmt::Display for MapCssDeclarationValueType {
@@ -174,9 +361,14 @@ impl fmt::Display for MapCssDeclarationValueType {
             LinecapDeclarationVariant(linecap) => write!(f, "{}", linecap),
             LinejoinDeclarationVariant(linejoin) => write!(f, "{}", linejoin),
             TextPositionDeclarationVariant(text_pos) => write!(f, "{}", text_pos),
+            TextAnchorHorizontalDeclarationVariant(anchor) => write!(f, "{}", anchor),
+            TextAnchorVerticalDeclarationVariant(anchor) => write!(f, "{}", anchor),
             IntegerArray(ints) => write!(f, "{:?}", ints),
             Integer(int) => write!(f, "{}", int),
             Float(float) => write!(f, "{}", float),
+            Length(length) => write!(f, "{}", length),
+            Tag(key, value) => write!(f, "{}={}", key, value),
+            Eval(expression, _) => write!(f, "eval({})", expression),
         }
     }
 }
@@ -207,6 +399,21 @@ impl ToFloatValue for MapCssDeclarationValueType {
     }
 }
 
+impl ToLengthValue for MapCssDeclarationValueType {
+    fn to_length(&self) -> Length {
+        use MapCssDeclarationValueType::*;
+
+        match self {
+            Length(length) => *length,
+            // unitless numbers are pixels
+            Float(float) => self::Length::pixels(*float),
+            Integer(int) => self::Length::pixels(*int as FloatSize),
+
+            _ => panic!("Unable to {:?} convert to length!", &self),
+        }
+    }
+}
+
 impl ToColorValue for MapCssDeclarationValueType {
     fn to_color(&self) -> RGBA {
         use MapCssDeclarationValueType::*;
@@ -231,19 +438,14 @@ impl ToBooleanValue for MapCssDeclarationValueType {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
 pub enum LinecapDeclarationVariant {
+    #[default]
     None,
     Round,
     Square,
 }
 
-impl Default for LinecapDeclarationVariant {
-    fn default() -> Self {
-        LinecapDeclarationVariant::None
-    }
-}
-
 impl fmt::Display for LinecapDeclarationVariant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use LinecapDeclarationVariant::*;
@@ -260,19 +462,14 @@ impl fmt::Display for LinecapDeclarationVariant {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
 pub enum LinejoinDeclarationVariant {
+    #[default]
     Round,
     Miter,
     Bevel,
 }
 
-impl Default for LinejoinDeclarationVariant {
-    fn default() -> Self {
-        LinejoinDeclarationVariant::Round
-    }
-}
-
 impl fmt::Display for LinejoinDeclarationVariant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use LinejoinDeclarationVariant::*;
@@ -289,18 +486,13 @@ impl fmt::Display for LinejoinDeclarationVariant {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
 pub enum TextPositionDeclarationVariant {
+    #[default]
     Center,
     Line,
 }
 
-impl Default for TextPositionDeclarationVariant {
-    fn default() -> Self {
-        TextPositionDeclarationVariant::Center
-    }
-}
-
 impl fmt::Display for TextPositionDeclarationVariant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use TextPositionDeclarationVariant::*;
@@ -316,123 +508,920 @@ impl fmt::Display for TextPositionDeclarationVariant {
     }
 }
 
-fn check_conditions(element_data: &Box<dyn ElementData>, condition: &SelectorCondition) -> bool {
+/// Where a label is placed horizontally relative to its point, e.g. `left` places it on the left
+/// of a node.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum TextAnchorHorizontalDeclarationVariant {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl fmt::Display for TextAnchorHorizontalDeclarationVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TextAnchorHorizontalDeclarationVariant::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Left => "left",
+                Center => "center",
+                Right => "right",
+            }
+        )
+    }
+}
+
+/// Where a label is placed vertically relative to its point, e.g. `below` places it beneath a
+/// node.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum TextAnchorVerticalDeclarationVariant {
+    Above,
+    #[default]
+    Center,
+    Below,
+}
+
+impl fmt::Display for TextAnchorVerticalDeclarationVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TextAnchorVerticalDeclarationVariant::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Above => "above",
+                Center => "center",
+                Below => "below",
+            }
+        )
+    }
+}
+
+fn check_conditions(context: &MatchContext<'_>, condition: &SelectorCondition) -> bool {
+    let element_data = context.element_data();
+
     use SelectorCondition::*;
 
     match condition {
         True => true,
         False => false,
 
-        Not(selector) => match selector.selector_type() {
-            SelectorType::Any => !check_conditions(element_data, selector.conditions()),
-            SelectorType::Canvas => {
-                if element_data.id() == ElementID::Canvas {
-                    !check_conditions(element_data, selector.conditions())
-                } else {
-                    true
-                }
-            }
+        Not(selector) => !matches_selector(context, selector),
 
-            _ => {
-                warn!(
-                    "todo add Not() implementation for {:?}",
-                    selector.selector_type()
-                );
+        ClosedPath => element_data.has_closed_path(),
 
-                true
-            }
-        },
+        PseudoClass(pseudo_class) => check_pseudo_class(context, pseudo_class),
 
-        ClosedPath => element_data.has_closed_path(),
+        ExactZoomLevel(zoom_level) => context
+            .zoom_level()
+            .map(|current_zoom_level| current_zoom_level == *zoom_level)
+            .unwrap_or(true),
 
-        ExactZoomLevel(_) | MinZoomLevel(_) | RangeZoomLevel(_, _) | MaxZoomLevel(_) => {
-            if !DID_BLAME_ZOOM_LEVEL_NOT_SUPPORTED.swap(true, Ordering::SeqCst) {
-                warn!("Zoom level specific declarations are currently not supported. Discarding these conditions.");
-            }
+        MinZoomLevel(zoom_level) => context
+            .zoom_level()
+            .map(|current_zoom_level| current_zoom_level >= *zoom_level)
+            .unwrap_or(true),
 
-            true
-        }
+        MaxZoomLevel(zoom_level) => context
+            .zoom_level()
+            .map(|current_zoom_level| current_zoom_level <= *zoom_level)
+            .unwrap_or(true),
+
+        RangeZoomLevel(min_zoom_level, max_zoom_level) => context
+            .zoom_level()
+            .map(|current_zoom_level| {
+                (*min_zoom_level..=*max_zoom_level).contains(&current_zoom_level)
+            })
+            .unwrap_or(true),
 
         List(condition_list) => condition_list
             .iter()
-            .all(|condition| check_conditions(element_data, condition)),
+            .all(|condition| check_conditions(context, condition)),
 
-        GenericPseudoClass(_pseudo_class) => {
-            if !DID_BLAME_HAS_PSEUDO_CLASS_NOT_SUPPORTED.swap(true, Ordering::SeqCst) {
-                warn!("Pseudo classes are currently not supported. Discarded condition.");
-            }
+        // the layer has already been matched before checking the conditions
+        GenericPseudoClass(_layer) => true,
 
-            true
-        }
+        HasClass(class) => context.has_class(class),
 
-        HasTag(condition_tag_key) => element_data
-            .tags()
-            .iter()
-            .any(|(element_tag_key, _element_tag_value)| element_tag_key == condition_tag_key),
+        HasTag(condition_tag_key) => context.tag(condition_tag_key).is_some(),
+
+        HasNotTag(condition_tag_key) => context.tag(condition_tag_key).is_none(),
+
+        TagIsTruthy(condition_tag_key) => context
+            .tag(condition_tag_key)
+            .map(|tag_value| TRUTHY_VALUES.contains(&tag_value))
+            .unwrap_or(false),
+
+        TagIsFalsy(condition_tag_key) => context
+            .tag(condition_tag_key)
+            .map(|tag_value| FALSY_VALUES.contains(&tag_value))
+            .unwrap_or(false),
 
         HasExactTagValue(condition_tag_key, condition_tag_value) => {
-            element_data
-                .tags()
-                .iter()
-                .any(|(element_tag_key, element_tag_value)| {
-                    element_tag_key == condition_tag_key && element_tag_value == condition_tag_value
-                })
+            context.tag(condition_tag_key) == Some(condition_tag_value.as_str())
         }
 
         HasNotTagValue(condition_tag_key, condition_tag_value) => {
-            element_data
-                .tags()
-                .iter()
-                .all(|(element_tag_key, element_tag_value)| {
-                    element_tag_key != condition_tag_key || element_tag_value != condition_tag_value
-                })
+            context.tag(condition_tag_key) != Some(condition_tag_value.as_str())
         }
 
-        ValueGreaterThanEqual(condition_tag_key, condition_tag_value) => element_data
-            .tags()
-            .iter()
-            .any(|(element_tag_key, element_tag_value)| {
-                if condition_tag_key != element_tag_key {
-                    return false;
-                }
+        TagValueMatches(condition_tag_key, regex) => context
+            .tag(condition_tag_key)
+            .map(|tag_value| regex.is_match(tag_value))
+            .unwrap_or(false),
 
-                element_tag_value
-                    .parse::<isize>()
-                    .map(|tag_value| &tag_value >= condition_tag_value)
-                    .unwrap_or(false)
-            }),
+        TagValueNotMatches(condition_tag_key, regex) => context
+            .tag(condition_tag_key)
+            .map(|tag_value| !regex.is_match(tag_value))
+            .unwrap_or(true),
 
-        ValueGreaterThan(condition_tag_key, condition_tag_value) => {
-            element_data
-                .tags()
-                .iter()
-                .any(|(element_tag_key, element_tag_value)| {
-                    if condition_tag_key != element_tag_key {
-                        return false;
-                    }
+        ValueGreaterThan(condition_tag_key, threshold) => {
+            compare_tag_number(context, condition_tag_key, |number| {
+                number > threshold.value()
+            })
+        }
 
-                    element_tag_value
-                        .parse::<isize>()
-                        .map(|tag_value| &tag_value > condition_tag_value)
-                        .unwrap_or(false)
-                })
+        ValueGreaterThanEqual(condition_tag_key, threshold) => {
+            compare_tag_number(context, condition_tag_key, |number| {
+                number >= threshold.value()
+            })
         }
 
-        HasDescendant(_) => {
-            if !DID_BLAME_HAS_DESCENDANT_NOT_SUPPORTED.swap(true, Ordering::SeqCst) {
-                warn!("The HasDescendant(_) condition is currently not supported. Discarding…");
-            }
+        ValueLessThan(condition_tag_key, threshold) => {
+            compare_tag_number(context, condition_tag_key, |number| {
+                number < threshold.value()
+            })
+        }
 
-            true
+        ValueLessThanEqual(condition_tag_key, threshold) => {
+            compare_tag_number(context, condition_tag_key, |number| {
+                number <= threshold.value()
+            })
         }
 
+        HasParent(parent_selector, link_conditions) => {
+            let Some(data_set) = context.data_set() else {
+                return false;
+            };
+            let id = element_data.id();
+
+            let matches_parent = |parent_data: &dyn ElementData| {
+                matches_selector(
+                    &MatchContext::new(parent_data, Some(data_set), context.zoom_level()),
+                    parent_selector,
+                )
+            };
+
+            let is_member_of_matching_relation =
+                data_set
+                    .parent_relations(&id)
+                    .any(|(relation_data, index)| {
+                        check_link_conditions(
+                            link_conditions,
+                            &relation_data.members[index].role,
+                            index,
+                            relation_data.members.len(),
+                        ) && matches_parent(relation_data)
+                    });
+
+            // nodes are the children of the ways referencing them
+            let is_node_of_matching_way = || {
+                let ElementID::Node(nid) = &id else {
+                    return false;
+                };
+
+                data_set
+                    .nid_to_node_data
+                    .get(nid)
+                    .into_iter()
+                    .flat_map(|node_data| &node_data.ways)
+                    .filter_map(|wid| data_set.wid_to_way_data.get(wid))
+                    .any(|way_data| {
+                        let refs = way_data.refs();
+
+                        refs.iter().enumerate().any(|(index, node_ref)| {
+                            node_ref == nid
+                                && check_link_conditions(link_conditions, "", index, refs.len())
+                        }) && matches_parent(way_data)
+                    })
+            };
+
+            is_member_of_matching_relation || is_node_of_matching_way()
+        }
+    }
+}
+
+/// Compares the number of the tag, which may have a unit like `50 mph`, with a threshold.
+fn compare_tag_number(
+    context: &MatchContext<'_>,
+    tag_key: &str,
+    comparison: impl Fn(FloatSize) -> bool,
+) -> bool {
+    context
+        .tag(tag_key)
+        .and_then(TagNumber::parse)
+        .map(|number| comparison(number.value()))
+        .unwrap_or(false)
+}
+
+/// Returns whether the element of the context is of the type of the selector and fulfills its
+/// conditions.
+fn matches_selector(context: &MatchContext<'_>, selector: &Selector) -> bool {
+    let element_data = context.element_data();
+
+    let matches_type = match (selector.selector_type(), element_data.id()) {
+        (SelectorType::Any, _)
+        | (SelectorType::Canvas, ElementID::Canvas)
+        | (SelectorType::Node, ElementID::Node(_))
+        | (SelectorType::Way, ElementID::Way(_))
+        | (SelectorType::Relation, ElementID::Relation(_)) => true,
+        (SelectorType::Area, ElementID::Way(_)) => element_data.has_closed_path(),
+        (SelectorType::Line, ElementID::Way(_)) => !element_data.has_closed_path(),
+        _ => false,
+    };
+
+    matches_type && check_conditions(context, selector.conditions())
+}
+
+/// Checks the conditions of the link between a parent and its child at the given index, which
+/// know the `role` of the membership and the 1-based `index`. Negative indices count from the
+/// end, i.e. `[index=-1]` matches the last child.
+fn check_link_conditions(
+    condition: &SelectorCondition,
+    role: &str,
+    index: usize,
+    child_count: usize,
+) -> bool {
+    use SelectorCondition::*;
+
+    let link_value = |key: &str, from_end: bool| match key {
+        "role" => Some(role.to_owned()),
+        "index" if from_end => Some((index as isize - child_count as isize).to_string()),
+        "index" => Some((index + 1).to_string()),
+        _ => None,
+    };
+    let link_number = |key: &str, threshold: &TagNumber| {
+        link_value(key, threshold.value() < 0.0)
+            .and_then(|value| TagNumber::parse(&value))
+            .map(|number| number.value())
+    };
+
+    match condition {
+        True => true,
+        False => false,
+
+        List(condition_list) => condition_list
+            .iter()
+            .all(|condition| check_link_conditions(condition, role, index, child_count)),
+
+        HasTag(key) => link_value(key, false)
+            .map(|value| !value.is_empty())
+            .unwrap_or(false),
+
+        HasExactTagValue(key, value) => {
+            link_value(key, value.starts_with('-')).as_deref() == Some(value.as_str())
+        }
+
+        HasNotTagValue(key, value) => {
+            link_value(key, value.starts_with('-')).as_deref() != Some(value.as_str())
+        }
+
+        TagValueMatches(key, regex) => link_value(key, false)
+            .map(|value| regex.is_match(&value))
+            .unwrap_or(false),
+
+        TagValueNotMatches(key, regex) => link_value(key, false)
+            .map(|value| !regex.is_match(&value))
+            .unwrap_or(true),
+
+        ValueGreaterThan(key, threshold) => link_number(key, threshold)
+            .map(|number| number > threshold.value())
+            .unwrap_or(false),
+        ValueGreaterThanEqual(key, threshold) => link_number(key, threshold)
+            .map(|number| number >= threshold.value())
+            .unwrap_or(false),
+        ValueLessThan(key, threshold) => link_number(key, threshold)
+            .map(|number| number < threshold.value())
+            .unwrap_or(false),
+        ValueLessThanEqual(key, threshold) => link_number(key, threshold)
+            .map(|number| number <= threshold.value())
+            .unwrap_or(false),
+
         condition => {
-            warn!(
-                "No steps defined to check condition {:?} on element {:?}!",
-                condition, element_data
-            );
+            warn!("Link conditions cannot test {:?}!", condition);
 
             false
         }
     }
 }
+
+/// Tag values matched by `[key?]` and `[key?!]`.
+pub(crate) const TRUTHY_VALUES: &[&str] = &["yes", "true", "1"];
+pub(crate) const FALSY_VALUES: &[&str] = &["no", "false", "0"];
+
+/// Keys of tags holding meta data, which do not make an element `:tagged`.
+const UNINTERESTING_KEYS: &[&str] = &[
+    "source",
+    "source_ref",
+    "note",
+    "comment",
+    "created_by",
+    "converted_by",
+    "fixme",
+    "FIXME",
+    "odbl",
+    "odbl:note",
+];
+
+fn check_pseudo_class(context: &MatchContext<'_>, pseudo_class: &PseudoClass) -> bool {
+    let element_data = context.element_data();
+    let id = element_data.id();
+
+    // the ways referencing the node, which are only known with a data set
+    let node_ways = || match (&id, context.data_set()) {
+        (ElementID::Node(nid), Some(data_set)) => data_set
+            .nid_to_node_data
+            .get(nid)
+            .map(|node_data| node_data.ways.len()),
+        _ => None,
+    };
+
+    let is_tagged = || {
+        element_data.tags().iter().any(|(tag_key, _)| {
+            !UNINTERESTING_KEYS.contains(&tag_key.as_str()) && !tag_key.starts_with("source:")
+        })
+    };
+
+    match pseudo_class {
+        PseudoClass::Unclosed => matches!(id, ElementID::Way(_)) && !element_data.has_closed_path(),
+        PseudoClass::Tagged => is_tagged(),
+        PseudoClass::Untagged => !is_tagged(),
+        PseudoClass::Area => match id {
+            ElementID::Way(_) | ElementID::Relation(_) => element_data.has_closed_path(),
+            _ => false,
+        },
+        PseudoClass::Connection => node_ways().map(|ways| ways > 1).unwrap_or(false),
+        PseudoClass::Unconnected => node_ways().map(|ways| ways == 0).unwrap_or(false),
+        PseudoClass::RightHandTraffic => context
+            .data_set()
+            .and_then(|data_set| data_set.center(&id))
+            .map(|(lat, lon)| is_right_hand_traffic(lat, lon))
+            .unwrap_or(false),
+        PseudoClass::New => match id {
+            ElementID::Node(id) | ElementID::Way(id) | ElementID::Relation(id) => id.get() < 0,
+            ElementID::Canvas => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ElementData, Length, MapCssDeclarationList, MapCssDeclarationProperty,
+        MapCssDeclarationValueType, RGBA,
+    };
+    use crate::data::{
+        DataSet, NodeData, RelationData, RelationMember, RelationMemberType, WayData,
+    };
+    use crate::mapcss::parser::MapCssParser;
+    use std::collections::HashMap;
+    use std::num::NonZeroI64;
+
+    fn way(tags: &[(&str, &str)]) -> WayData {
+        WayData::new(
+            NonZeroI64::new(1).unwrap(),
+            tags.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            vec![NonZeroI64::new(1).unwrap(), NonZeroI64::new(2).unwrap()],
+        )
+    }
+
+    /// Parses the stylesheet and returns the value of the property for an open way with the tags.
+    pub(crate) fn cascade(
+        stylesheet: &str,
+        tags: &[(&str, &str)],
+        property: MapCssDeclarationProperty,
+    ) -> Option<MapCssDeclarationValueType> {
+        let (_, rules, _) = MapCssParser::parse_mapcss(stylesheet).unwrap();
+
+        MapCssDeclarationList::new(rules)
+            .search_cascading(Box::new(way(tags)), &property)
+            .map(|value| value.into_owned())
+    }
+
+    pub(crate) fn width(pixels: f64) -> Option<MapCssDeclarationValueType> {
+        Some(MapCssDeclarationValueType::Length(Length::pixels(pixels)))
+    }
+
+    pub(crate) fn text(string: &str) -> Option<MapCssDeclarationValueType> {
+        Some(MapCssDeclarationValueType::String(string.to_owned()))
+    }
+
+    #[test]
+    fn test_set_classes_and_tags_in_cascade_order() {
+        let stylesheet = r#"
+            *[highway=primary] { set .major_road; set ref_label="B 191"; }
+            way.major_road { width: 3; text: eval(tag("ref_label")); }
+            way!.major_road { width: 1; }
+            way.late { color: #ff0000; }
+            line[highway] { set .late; }
+            "#;
+
+        let lookup = |highway, property| cascade(stylesheet, &[("highway", highway)], property);
+
+        assert_eq!(
+            lookup("primary", MapCssDeclarationProperty::Width),
+            width(3.0)
+        );
+        assert_eq!(
+            lookup("primary", MapCssDeclarationProperty::Text),
+            text("B 191")
+        );
+        assert_eq!(
+            lookup("residential", MapCssDeclarationProperty::Width),
+            width(1.0)
+        );
+        assert_eq!(lookup("residential", MapCssDeclarationProperty::Text), None);
+
+        // the class is only set after the way.late {} rule was cascaded
+        assert_eq!(lookup("primary", MapCssDeclarationProperty::Color), None);
+    }
+
+    #[test]
+    fn test_later_rules_win() {
+        let lookup = |property| {
+            cascade(
+                r#"
+                way[highway] { width: 1; }
+                way[highway=primary] { width: 2; }
+                *[highway] { width: 3; color: #ff0000; }
+                way[highway] { color: #0000ff; }
+                line { z-index: 1; }
+                way { z-index: 2; }
+                "#,
+                &[("highway", "primary")],
+                property,
+            )
+        };
+
+        assert_eq!(lookup(MapCssDeclarationProperty::Width), width(3.0));
+        assert_eq!(
+            lookup(MapCssDeclarationProperty::Color),
+            Some(MapCssDeclarationValueType::Color(
+                "#0000ff".parse::<RGBA>().unwrap()
+            ))
+        );
+        assert_eq!(
+            lookup(MapCssDeclarationProperty::ZIndex),
+            Some(MapCssDeclarationValueType::Float(2.0))
+        );
+    }
+
+    #[test]
+    fn test_layers_are_cascaded_separately() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            way[highway] { width: 2; z-index: 3; }
+            way[highway]::casing { width: 4; z-index: 2; }
+            way[bridge]::bridge { width: 6; }
+            way::* { color: #000000; }
+            "#,
+        )
+        .unwrap();
+
+        let mapcss_ast = MapCssDeclarationList::new(rules);
+        assert_eq!(mapcss_ast.layers(), vec!["default", "bridge", "casing"]);
+
+        let way_data = way(&[("highway", "primary")]);
+        let lookup = |layer, property| {
+            mapcss_ast
+                .search_cascading_in_layer(Box::new(way_data.clone()), None, layer, &property)
+                .map(|value| value.into_owned())
+        };
+
+        assert_eq!(
+            lookup("default", MapCssDeclarationProperty::Width),
+            width(2.0)
+        );
+        assert_eq!(
+            lookup("casing", MapCssDeclarationProperty::Width),
+            width(4.0)
+        );
+        assert_eq!(
+            lookup("casing", MapCssDeclarationProperty::ZIndex),
+            Some(MapCssDeclarationValueType::Float(2.0))
+        );
+        assert_eq!(lookup("bridge", MapCssDeclarationProperty::Width), None);
+        // ::* applies to every layer
+        assert!(lookup("bridge", MapCssDeclarationProperty::Color).is_some());
+        assert!(lookup("default", MapCssDeclarationProperty::Color).is_some());
+    }
+
+    #[test]
+    fn test_pseudo_classes() {
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(
+            r#"
+            way:unclosed { text: "unclosed"; }
+            way:area { text: "area"; }
+            way:untagged { z-index: 1; }
+            way:tagged { z-index: 2; }
+            way:righthandtraffic { width: 2; }
+            way:new { color: #ff0000; }
+            way:modified { opacity: 0.5; }
+            node:connection { text: "connection"; }
+            node:unconnected { text: "unconnected"; }
+            way:unknown { width: 9; }
+            "#,
+        )
+        .unwrap();
+
+        // the extracts do not tell which elements were modified
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| warning.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Dropped selector with unsupported pseudo class :modified.",
+                "Dropped selector with unknown pseudo class :unknown.",
+            ]
+        );
+
+        let mapcss_ast = MapCssDeclarationList::new(rules);
+
+        let nid = |nid| NonZeroI64::new(nid).unwrap();
+        let node = |id, lat, lon, ways: Vec<i64>| {
+            (
+                nid(id),
+                NodeData {
+                    nid: nid(id),
+                    lat,
+                    lon,
+                    tags: vec![],
+                    ways: ways.into_iter().map(nid).collect(),
+                },
+            )
+        };
+
+        // a road in Berlin joining another way at node 2 and a new building in London
+        let nid_to_node_data = HashMap::from([
+            node(1, 52.52, 13.40, vec![1]),
+            node(2, 52.53, 13.41, vec![1, 3]),
+            node(3, 51.51, -0.13, vec![-2]),
+            node(4, 51.52, -0.12, vec![-2]),
+            node(5, 51.53, -0.11, vec![]),
+            node(6, 51.51, -0.11, vec![-2]),
+        ]);
+        let road = WayData::new(
+            nid(1),
+            vec![("source".to_owned(), "survey".to_owned())],
+            vec![nid(1), nid(2)],
+        );
+        let building = WayData::new(
+            nid(-2),
+            vec![("building".to_owned(), "yes".to_owned())],
+            vec![nid(3), nid(4), nid(6), nid(3)],
+        );
+        let wid_to_way_data = HashMap::from([(nid(1), road.clone()), (nid(-2), building.clone())]);
+        let rid_to_relation_data = HashMap::new();
+        let data_set = DataSet::new(&nid_to_node_data, &wid_to_way_data, &rid_to_relation_data);
+
+        let lookup = |element_data: Box<dyn ElementData>, property| {
+            mapcss_ast
+                .search_cascading_in_layer(element_data, Some(&data_set), "default", &property)
+                .map(|value| value.into_owned())
+        };
+
+        assert_eq!(
+            lookup(Box::new(road.clone()), MapCssDeclarationProperty::Text),
+            text("unclosed")
+        );
+        assert_eq!(
+            lookup(Box::new(building.clone()), MapCssDeclarationProperty::Text),
+            text("area")
+        );
+        // source is meta data only
+        assert_eq!(
+            lookup(Box::new(road.clone()), MapCssDeclarationProperty::ZIndex),
+            Some(MapCssDeclarationValueType::Float(1.0))
+        );
+        assert_eq!(
+            lookup(
+                Box::new(building.clone()),
+                MapCssDeclarationProperty::ZIndex
+            ),
+            Some(MapCssDeclarationValueType::Float(2.0))
+        );
+        assert_eq!(
+            lookup(Box::new(road.clone()), MapCssDeclarationProperty::Width),
+            width(2.0)
+        );
+        assert_eq!(
+            lookup(Box::new(building.clone()), MapCssDeclarationProperty::Width),
+            None
+        );
+        assert!(lookup(Box::new(road), MapCssDeclarationProperty::Color).is_none());
+        assert!(lookup(Box::new(building), MapCssDeclarationProperty::Color).is_some());
+
+        let node_text = |id| {
+            lookup(
+                Box::new(nid_to_node_data[&nid(id)].clone()),
+                MapCssDeclarationProperty::Text,
+            )
+        };
+        assert_eq!(node_text(2), text("connection"));
+        assert_eq!(node_text(3), None);
+        assert_eq!(node_text(5), text("unconnected"));
+    }
+
+    #[test]
+    fn test_parent_selectors() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            relation[type=route][route=bus] > way { color: #ff0000; }
+            relation[type=multipolygon] >[role=outer] way { width: 3; }
+            way[highway] >[index=1] node { text: "first"; }
+            way[highway] >[index=-1] node { text: "last"; }
+            way[highway] > node[highway=crossing] { z-index: 4; }
+            relation[route=bus] > way > node { opacity: 0.5; }
+            "#,
+        )
+        .unwrap();
+
+        let mapcss_ast = MapCssDeclarationList::new(rules);
+
+        let nid = |nid| NonZeroI64::new(nid).unwrap();
+        let node = |id, tags: Vec<(String, String)>, ways: Vec<i64>| {
+            (
+                nid(id),
+                NodeData {
+                    nid: nid(id),
+                    lat: 47.0,
+                    lon: 9.0,
+                    tags,
+                    ways: ways.into_iter().map(nid).collect(),
+                },
+            )
+        };
+        let member = |wid, role: &str| RelationMember {
+            member: RelationMemberType::Way(nid(wid)),
+            role: role.to_owned(),
+        };
+        let tags = |tags: &[(&str, &str)]| {
+            tags.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let nid_to_node_data = HashMap::from([
+            node(1, vec![], vec![1]),
+            node(2, tags(&[("highway", "crossing")]), vec![1]),
+            node(3, vec![], vec![1, 2]),
+            node(4, vec![], vec![2]),
+        ]);
+        let road = WayData::new(
+            nid(1),
+            tags(&[("highway", "primary")]),
+            vec![nid(1), nid(2), nid(3)],
+        );
+        let other_road = WayData::new(
+            nid(2),
+            tags(&[("highway", "primary")]),
+            vec![nid(4), nid(3)],
+        );
+        let wid_to_way_data = HashMap::from([(nid(1), road.clone()), (nid(2), other_road.clone())]);
+        let rid_to_relation_data = HashMap::from([
+            (
+                nid(1),
+                RelationData {
+                    rid: nid(1),
+                    tags: tags(&[("type", "route"), ("route", "bus")]),
+                    members: vec![member(1, "")],
+                },
+            ),
+            (
+                nid(2),
+                RelationData {
+                    rid: nid(2),
+                    tags: tags(&[("type", "multipolygon")]),
+                    members: vec![member(1, "inner"), member(2, "outer")],
+                },
+            ),
+        ]);
+        let data_set = DataSet::new(&nid_to_node_data, &wid_to_way_data, &rid_to_relation_data);
+
+        let lookup = |element_data: Box<dyn ElementData>, property| {
+            mapcss_ast
+                .search_cascading_in_layer(element_data, Some(&data_set), "default", &property)
+                .map(|value| value.into_owned())
+        };
+        let node_lookup =
+            |id, property| lookup(Box::new(nid_to_node_data[&nid(id)].clone()), property);
+
+        // the rules style the children, not the parents
+        assert!(lookup(Box::new(road.clone()), MapCssDeclarationProperty::Color).is_some());
+        assert!(lookup(
+            Box::new(other_road.clone()),
+            MapCssDeclarationProperty::Color
+        )
+        .is_none());
+        assert!(lookup(
+            Box::new(rid_to_relation_data[&nid(1)].clone()),
+            MapCssDeclarationProperty::Color
+        )
+        .is_none());
+
+        assert_eq!(
+            lookup(Box::new(road), MapCssDeclarationProperty::Width),
+            None
+        );
+        assert_eq!(
+            lookup(Box::new(other_road), MapCssDeclarationProperty::Width),
+            width(3.0)
+        );
+
+        assert_eq!(
+            node_lookup(1, MapCssDeclarationProperty::Text),
+            text("first")
+        );
+        assert_eq!(
+            node_lookup(4, MapCssDeclarationProperty::Text),
+            text("first")
+        );
+        assert_eq!(node_lookup(2, MapCssDeclarationProperty::Text), None);
+        assert_eq!(
+            node_lookup(3, MapCssDeclarationProperty::Text),
+            text("last")
+        );
+        assert!(node_lookup(2, MapCssDeclarationProperty::ZIndex).is_some());
+        assert!(node_lookup(3, MapCssDeclarationProperty::ZIndex).is_none());
+        assert!(node_lookup(2, MapCssDeclarationProperty::Opacity).is_some());
+
+        // without the other elements, parents are unknown
+        assert!(mapcss_ast
+            .search_cascading(
+                Box::new(nid_to_node_data[&nid(2)].clone()),
+                &MapCssDeclarationProperty::ZIndex
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let lookup = |key, value, property| {
+            cascade(
+                r#"
+                way[ele>1500.5] { text: "high"; }
+                way[ele<=-10] { text: "below sea level"; }
+                way[maxspeed<60] { color: #ff0000; }
+                way[width>=3] { width: 3; }
+                way[ele>high] { z-index: 1; }
+                "#,
+                &[(key, value)],
+                property,
+            )
+        };
+
+        assert_eq!(
+            lookup("ele", "1500.7", MapCssDeclarationProperty::Text),
+            text("high")
+        );
+        assert_eq!(lookup("ele", "1500", MapCssDeclarationProperty::Text), None);
+        assert_eq!(
+            lookup("ele", "-12", MapCssDeclarationProperty::Text),
+            text("below sea level")
+        );
+        assert_eq!(lookup("ele", "high", MapCssDeclarationProperty::Text), None);
+
+        // 50 mph are about 80 km/h
+        assert!(lookup("maxspeed", "50", MapCssDeclarationProperty::Color).is_some());
+        assert!(lookup("maxspeed", "50 mph", MapCssDeclarationProperty::Color).is_none());
+        assert!(lookup("maxspeed", "walk", MapCssDeclarationProperty::Color).is_none());
+
+        assert!(lookup("width", "3.5 m", MapCssDeclarationProperty::Width).is_some());
+        assert!(lookup("width", "3", MapCssDeclarationProperty::Width).is_some());
+        assert!(lookup("width", "250 cm", MapCssDeclarationProperty::Width).is_none());
+
+        // the comparison with a word is dropped
+        assert!(lookup("ele", "2000", MapCssDeclarationProperty::ZIndex).is_none());
+    }
+
+    #[test]
+    fn test_negated_and_truthy_tag_tests() {
+        let lookup = |tags: &[(&str, &str)], property| {
+            cascade(
+                r#"
+                way[!name] { text: "unnamed"; }
+                way[oneway?] { width: 2; }
+                way[oneway?!] { width: 1; }
+                way[!oneway?] { color: #ff0000; }
+                "#,
+                tags,
+                property,
+            )
+        };
+
+        assert!(lookup(&[], MapCssDeclarationProperty::Text).is_some());
+        assert!(lookup(&[("name", "Hauptstraße")], MapCssDeclarationProperty::Text).is_none());
+
+        assert_eq!(
+            lookup(&[("oneway", "yes")], MapCssDeclarationProperty::Width),
+            width(2.0)
+        );
+        assert_eq!(
+            lookup(&[("oneway", "1")], MapCssDeclarationProperty::Width),
+            width(2.0)
+        );
+        assert_eq!(
+            lookup(&[("oneway", "no")], MapCssDeclarationProperty::Width),
+            width(1.0)
+        );
+        // -1 is neither truthy nor falsy
+        assert_eq!(
+            lookup(&[("oneway", "-1")], MapCssDeclarationProperty::Width),
+            None
+        );
+        assert_eq!(lookup(&[], MapCssDeclarationProperty::Width), None);
+
+        assert!(lookup(&[("oneway", "-1")], MapCssDeclarationProperty::Color).is_some());
+        assert!(lookup(&[], MapCssDeclarationProperty::Color).is_some());
+        assert!(lookup(&[("oneway", "true")], MapCssDeclarationProperty::Color).is_none());
+    }
+
+    #[test]
+    fn test_zoom_level_conditions() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            way|z-11 { width: 1; }
+            way|z12-13 { width: 2; }
+            way|z14- { width: 3; }
+            @media not (max-zoom: 13) { way { text: "detailed"; } }
+            "#,
+        )
+        .unwrap();
+
+        let mut mapcss_ast = MapCssDeclarationList::new(rules);
+
+        let mut lookup = |zoom_level, property| {
+            mapcss_ast.set_zoom_level(zoom_level);
+
+            mapcss_ast
+                .search_cascading(Box::new(way(&[])), &property)
+                .map(|value| value.into_owned())
+        };
+
+        assert_eq!(lookup(8.0, MapCssDeclarationProperty::Width), width(1.0));
+        // fractional zoom levels are rounded down
+        assert_eq!(lookup(11.9, MapCssDeclarationProperty::Width), width(1.0));
+        assert_eq!(lookup(13.0, MapCssDeclarationProperty::Width), width(2.0));
+        assert_eq!(lookup(17.0, MapCssDeclarationProperty::Width), width(3.0));
+
+        assert_eq!(lookup(13.5, MapCssDeclarationProperty::Text), None);
+        assert!(lookup(14.0, MapCssDeclarationProperty::Text).is_some());
+    }
+
+    #[test]
+    fn test_regex_conditions() {
+        let lookup = |tags: &[(&str, &str)], property| {
+            cascade(
+                r#"
+                way[highway=~/^(primary|secondary)$/] { width: 3; }
+                way[name=~/^b/i] { text: "starts with b"; }
+                way[ref=~/^A\/1$/] { z-index: 5; }
+                way[highway!~/^p/] { color: #ff0000; }
+                way[name=~/(/] { width: 10; }
+                "#,
+                tags,
+                property,
+            )
+        };
+
+        let primary = [
+            ("highway", "primary"),
+            ("name", "Bahnhofstrasse"),
+            ("ref", "A/1"),
+        ];
+
+        assert_eq!(
+            lookup(&primary, MapCssDeclarationProperty::Width),
+            width(3.0)
+        );
+        assert!(lookup(&primary, MapCssDeclarationProperty::Text).is_some());
+        assert!(lookup(&primary, MapCssDeclarationProperty::ZIndex).is_some());
+        assert_eq!(lookup(&primary, MapCssDeclarationProperty::Color), None);
+
+        assert_eq!(
+            lookup(
+                &[("highway", "primary_link")],
+                MapCssDeclarationProperty::Width
+            ),
+            None
+        );
+        // !~ also matches if the tag is not set
+        assert!(lookup(&[], MapCssDeclarationProperty::Color).is_some());
+    }
+}
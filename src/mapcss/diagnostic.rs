@@ -0,0 +1,171 @@
+use super::parser::Rule;
+use pest::error::LineColLocation;
+use pest::Span;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// the stylesheet could not be used at all
+    Error,
+    /// a part of the stylesheet was dropped, the rest is still used
+    Warning,
+}
+
+/// A problem in a stylesheet together with its location, rendered like a compiler message:
+///
+/// ```text
+/// warning: Dropped unknown declaration name "colr".
+///   --> style.mapcss:12:5
+///    |
+/// 12 |     colr: red;
+///    |     ^^^^^^^^^^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// the name of the stylesheet, if it was read from a file
+    pub file_name: Option<String>,
+    /// the 1-based line of the problem
+    pub line: usize,
+    /// the 1-based column of the problem, counted in characters
+    pub column: usize,
+    /// the whole line of the stylesheet containing the problem
+    pub source_line: String,
+    /// the number of characters to highlight, at least one
+    pub length: usize,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic pointing at the given part of the stylesheet. Spans reaching over
+    /// multiple lines are highlighted until the end of their first line.
+    pub fn from_span(severity: Severity, message: String, span: Span<'_>) -> Diagnostic {
+        let start = span.start_pos();
+        let (line, column) = start.line_col();
+        let source_line = start.line_of().trim_end_matches(['\r', '\n']).to_owned();
+
+        let remaining_length = source_line.chars().count().saturating_sub(column - 1);
+        let length = span
+            .as_str()
+            .lines()
+            .next()
+            .map(|first_line| first_line.chars().count())
+            .unwrap_or(0)
+            .min(remaining_length)
+            .max(1);
+
+        Diagnostic {
+            severity,
+            message,
+            file_name: None,
+            line,
+            column,
+            source_line,
+            length,
+        }
+    }
+
+    /// Converts a syntax error of the grammar, which makes the stylesheet unusable.
+    pub fn from_syntax_error(error: pest::error::Error<Rule>) -> Diagnostic {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(line_col) | LineColLocation::Span(line_col, _) => line_col,
+        };
+
+        Diagnostic {
+            severity: Severity::Error,
+            message: format!("Syntax error, {}.", error.variant.message()),
+            file_name: None,
+            line,
+            column,
+            source_line: error.line().trim_end_matches(['\r', '\n']).to_owned(),
+            length: 1,
+        }
+    }
+
+    pub fn with_file_name(mut self, file_name: &str) -> Diagnostic {
+        self.file_name = Some(file_name.to_owned());
+        self
+    }
+}
+
+impl Error for Diagnostic {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        writeln!(
+            f,
+            "{}: {}",
+            match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            self.message
+        )?;
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter,
+            self.file_name.as_deref().unwrap_or("<stylesheet>"),
+            self.line,
+            self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(
+            f,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column - 1),
+            "^".repeat(self.length)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Severity};
+    use crate::mapcss::parser::{MapCssParser, Rule};
+    use pest::Parser;
+
+    #[test]
+    fn test_render_diagnostic() {
+        let stylesheet = "way {\n    colr: red;\n}";
+        let declaration = MapCssParser::parse(Rule::rule, stylesheet)
+            .unwrap()
+            .next()
+            .unwrap()
+            .into_inner()
+            .nth(1)
+            .unwrap();
+
+        let diagnostic = Diagnostic::from_span(
+            Severity::Warning,
+            "Dropped unknown declaration name \"colr\".".to_owned(),
+            declaration.as_span(),
+        )
+        .with_file_name("style.mapcss");
+
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 5));
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning: Dropped unknown declaration name \"colr\".\n \
+             --> style.mapcss:2:5\n  \
+             |\n\
+             2 |     colr: red;\n  \
+             |     ^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let diagnostic =
+            MapCssParser::parse_mapcss("way { color: red; }\nway[highway=primary] {").unwrap_err();
+
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.line, 2);
+        assert_eq!(diagnostic.source_line, "way[highway=primary] {");
+    }
+}
@@ -1,13 +1,49 @@
+use super::MapCssParseError;
 use std::error::Error;
 use std::fmt;
 
 #[derive(Debug)]
 pub enum MapCssError {
     UnknownDeclarationName(String),
+    UnknownFunctionName(String),
+    UnknownPseudoClass(String),
+    /// holds a pseudo class that is known, but cannot be evaluated on the map data
+    UnsupportedPseudoClass(String),
+    InvalidArgumentCount {
+        function_name: &'static str,
+        argument_count: usize,
+    },
     IllegalDeclaration {
         declaration_name: String,
         illegal_context: &'static str,
     },
+    InvalidDeclarationValue {
+        declaration_name: String,
+        error: MapCssParseError,
+    },
+    InvalidRegex {
+        pattern: String,
+        reason: String,
+    },
+    InvalidSelector {
+        selector: String,
+        error: MapCssParseError,
+    },
+    /// holds the value a tag is compared with by <, <=, > or >=
+    NonNumericComparison(String),
+    /// holds what was dropped, e.g. "rule" or "declaration"
+    InvalidSyntax(&'static str),
+    /// holds the selector whose conditions contradict each other
+    UnsatisfiableSelector(String),
+    /// a meta selector listed together with other selectors, like `meta, way`
+    MetaSelectorInList,
+    /// a meta selector with conditions, like `meta[foo]` or `meta` within `@media`
+    MetaSelectorWithConditions,
+    /// holds the condition of a @media or @supports block like `(min-zoom: abc)`
+    InvalidBlockCondition {
+        condition: String,
+        error: MapCssParseError,
+    },
 }
 
 impl Error for MapCssError {}
@@ -22,6 +58,29 @@ impl fmt::Display for MapCssError {
                 "Dropped unknown declaration name \"{}\".",
                 declaration_name
             ),
+            UnknownFunctionName(function_name) => write!(
+                f,
+                "Dropped declaration calling unknown function \"{}\".",
+                function_name
+            ),
+            UnknownPseudoClass(pseudo_class) => write!(
+                f,
+                "Dropped selector with unknown pseudo class :{}.",
+                pseudo_class
+            ),
+            UnsupportedPseudoClass(pseudo_class) => write!(
+                f,
+                "Dropped selector with unsupported pseudo class :{}.",
+                pseudo_class
+            ),
+            InvalidArgumentCount {
+                function_name,
+                argument_count,
+            } => write!(
+                f,
+                "Dropped declaration calling {}() with {} arguments.",
+                function_name, argument_count
+            ),
             IllegalDeclaration {
                 declaration_name,
                 illegal_context,
@@ -30,6 +89,44 @@ impl fmt::Display for MapCssError {
                 "Declaration {} must not appear in {} block! Block dropped.",
                 declaration_name, illegal_context
             ),
+            InvalidDeclarationValue {
+                declaration_name,
+                error,
+            } => write!(
+                f,
+                "Dropped declaration {} with invalid value: {}.",
+                declaration_name, error
+            ),
+            InvalidRegex { pattern, reason } => write!(
+                f,
+                "Dropped selector with invalid regular expression /{}/: {}",
+                pattern, reason
+            ),
+            InvalidSelector { selector, error } => {
+                write!(f, "Dropped invalid selector {}: {}.", selector, error)
+            }
+            NonNumericComparison(value) => write!(
+                f,
+                "Dropped selector comparing with non-numeric value \"{}\".",
+                value
+            ),
+            InvalidSyntax(dropped) => write!(f, "Dropped {} with invalid syntax.", dropped),
+            UnsatisfiableSelector(selector) => {
+                write!(f, "Dropped selector {} which can never match.", selector)
+            }
+            MetaSelectorInList => write!(
+                f,
+                "Dropped meta selector, which must not share its block with other selectors."
+            ),
+            MetaSelectorWithConditions => write!(
+                f,
+                "Dropped meta selector, which must not have any conditions."
+            ),
+            InvalidBlockCondition { condition, error } => write!(
+                f,
+                "Dropped block with invalid condition {}: {}.",
+                condition, error
+            ),
         }
     }
 }
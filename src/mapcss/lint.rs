@@ -0,0 +1,251 @@
+use super::declaration::{MapCssDeclaration, MapCssDeclarationProperty};
+use super::diagnostic::{Diagnostic, Severity};
+use super::parser::{MapCssParser, ParsedRule};
+use super::selectors::SelectorType;
+use pest::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A problem that does not stop the stylesheet from being used, but is most likely a mistake.
+#[derive(Debug)]
+enum Lint {
+    /// holds the declaration name
+    MetaPropertyOutsideMeta(String),
+    DuplicateRule {
+        line: usize,
+    },
+    ShadowedDeclaration {
+        declaration_name: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Lint::*;
+
+        match self {
+            MetaPropertyOutsideMeta(declaration_name) => write!(
+                f,
+                "Declaration {} has no effect outside of the meta {{}} block.",
+                declaration_name
+            ),
+            DuplicateRule { line } => write!(f, "Duplicate of the rule at line {}.", line),
+            ShadowedDeclaration {
+                declaration_name,
+                line,
+            } => write!(
+                f,
+                "Declaration {} is always overridden by the one at line {}.",
+                declaration_name, line
+            ),
+        }
+    }
+}
+
+/// Checks the stylesheet for mistakes: everything the parser drops, like unknown properties,
+/// values of the wrong type or impossible zoom ranges, as well as meta properties outside of
+/// `meta {}`, duplicate rules and declarations overridden by a later one of the same selector.
+/// The diagnostics are sorted by their location, syntax errors are returned as error.
+pub fn lint_mapcss(mapcss: &str, file_name: Option<&str>) -> Result<Vec<Diagnostic>, Diagnostic> {
+    let (parsed_rules, mut diagnostics) = MapCssParser::parse_rules(mapcss, file_name)?;
+
+    let mut lint = |lint: Lint, span: Span<'_>| {
+        let diagnostic = Diagnostic::from_span(Severity::Warning, lint.to_string(), span);
+
+        diagnostics.push(match file_name {
+            Some(file_name) => diagnostic.with_file_name(file_name),
+            None => diagnostic,
+        });
+    };
+
+    // the declarations not overridden yet of each selector seen so far, as indices of the rule
+    // and of the declaration within
+    let mut selector_declarations: Vec<(_, Vec<(usize, usize)>)> = Vec::new();
+    // how many selectors of the rule of a declaration override it
+    let mut override_counts: HashMap<(usize, usize), usize> = HashMap::new();
+    let declaration_at = |(rule_index, declaration_index): (usize, usize)| {
+        &parsed_rules[rule_index].declarations[declaration_index]
+    };
+
+    for (index, parsed_rule) in parsed_rules.iter().enumerate() {
+        if parsed_rule
+            .selectors
+            .iter()
+            .any(|selector| selector.selector_type() != SelectorType::Meta)
+        {
+            for (declaration, span) in &parsed_rule.declarations {
+                if is_meta_property(&declaration.0) {
+                    lint(
+                        Lint::MetaPropertyOutsideMeta(declaration_name(*span).to_owned()),
+                        *span,
+                    );
+                }
+            }
+        }
+
+        if let Some(duplicate) = parsed_rules[..index]
+            .iter()
+            .find(|earlier_rule| is_duplicate(earlier_rule, parsed_rule))
+        {
+            lint(
+                Lint::DuplicateRule {
+                    line: duplicate.span.start_pos().line_col().0,
+                },
+                parsed_rule.span,
+            );
+            continue;
+        }
+
+        for selector in &parsed_rule.selectors {
+            let declarations = match selector_declarations
+                .iter()
+                .position(|(earlier_selector, _)| earlier_selector == &selector)
+            {
+                Some(position) => &mut selector_declarations[position].1,
+                None => {
+                    selector_declarations.push((selector, Vec::new()));
+                    &mut selector_declarations.last_mut().unwrap().1
+                }
+            };
+
+            for (declaration_index, (declaration, span)) in
+                parsed_rule.declarations.iter().enumerate()
+            {
+                let property = declaration.0;
+
+                // every set instruction applies, they never override each other
+                if property == MapCssDeclarationProperty::SetClass
+                    || property == MapCssDeclarationProperty::SetTag
+                {
+                    continue;
+                }
+
+                if let Some(position) = declarations.iter().position(|&earlier_declaration| {
+                    let ((earlier_property, _), _) = declaration_at(earlier_declaration);
+                    *earlier_property == property
+                }) {
+                    let overridden = declarations.remove(position);
+                    let override_count = override_counts.entry(overridden).or_default();
+                    *override_count += 1;
+
+                    // a declaration of a selector list may still apply to the other selectors
+                    if *override_count == parsed_rules[overridden.0].selectors.len() {
+                        let (_, earlier_span) = *declaration_at(overridden);
+                        lint(
+                            Lint::ShadowedDeclaration {
+                                declaration_name: declaration_name(earlier_span).to_owned(),
+                                line: span.start_pos().line_col().0,
+                            },
+                            earlier_span,
+                        );
+                    }
+                }
+
+                declarations.push((index, declaration_index));
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+
+    Ok(diagnostics)
+}
+
+fn is_meta_property(property: &MapCssDeclarationProperty) -> bool {
+    matches!(
+        property,
+        MapCssDeclarationProperty::Title
+            | MapCssDeclarationProperty::Version
+            | MapCssDeclarationProperty::Description
+            | MapCssDeclarationProperty::Acknowledgement
+    )
+}
+
+fn is_duplicate(earlier_rule: &ParsedRule<'_>, rule: &ParsedRule<'_>) -> bool {
+    let declarations = |rule: &ParsedRule<'_>| -> Vec<MapCssDeclaration> {
+        rule.declarations
+            .iter()
+            .map(|(declaration, _)| declaration.clone())
+            .collect()
+    };
+
+    earlier_rule.selectors == rule.selectors && declarations(earlier_rule) == declarations(rule)
+}
+
+/// The name of a declaration as written in the stylesheet, e.g. `width` of `width: 2;`.
+fn declaration_name<'i>(span: Span<'i>) -> &'i str {
+    span.as_str()
+        .split([':', ';'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_mapcss;
+
+    #[test]
+    fn test_lint_stylesheet() {
+        let diagnostics = lint_mapcss(
+            r#"meta { title: "Test"; }
+way { colr: red; width: thick; }
+way|z12-10 { width: 1; }
+node { title: "Node"; width: 1; width: 2; }
+way[highway] { width: 1; }
+way[highway] { color: red; width: 2; }
+area { color: red; }
+area { color: red; }
+line, area { width: 1; }
+line { width: 2; }
+meta[foo] { title: "Test"; }
+@media (min-zoom: abc) { way { width: 3; } }
+"#,
+            Some("style.mapcss"),
+        )
+        .unwrap();
+
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                (2, "Dropped unknown declaration name \"colr\"."),
+                (
+                    2,
+                    "Dropped declaration width with invalid value: invalid unit, expected a \
+                     number."
+                ),
+                (3, "Dropped invalid selector |z12-10: value out of range."),
+                (
+                    4,
+                    "Declaration title has no effect outside of the meta {} block."
+                ),
+                (
+                    4,
+                    "Declaration width is always overridden by the one at line 4."
+                ),
+                (
+                    5,
+                    "Declaration width is always overridden by the one at line 6."
+                ),
+                (8, "Duplicate of the rule at line 7."),
+                (
+                    11,
+                    "Dropped meta selector, which must not have any conditions."
+                ),
+                (
+                    12,
+                    "Dropped block with invalid condition (min-zoom: abc): expected an integer."
+                ),
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.file_name.as_deref() == Some("style.mapcss")));
+    }
+}
@@ -1,9 +1,13 @@
 pub mod declaration;
+pub mod diagnostic;
 pub mod error;
+pub mod lint;
 pub mod parser;
+pub mod printer;
 pub mod selectors;
 
-use declaration::{MapCssDeclaration, MapCssDeclarationProperty};
+use declaration::{ColorParseError, MapCssDeclaration, MapCssDeclarationProperty};
+use std::fmt;
 
 #[derive(Debug, Default)]
 pub struct MapCssAcknowledgement {
@@ -61,4 +65,31 @@ pub enum MapCssParseError {
     /// holds the expected unit
     InvalidUnit(&'static str),
     OutOfRange,
+    InvalidColor(ColorParseError),
+    /// holds the keywords that are allowed
+    InvalidKeyword(&'static str),
+    NotASingleCharacter,
+    NotAnInteger,
+}
+
+impl fmt::Display for MapCssParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MapCssParseError::*;
+
+        match self {
+            InvalidSelector => write!(f, "invalid selector"),
+            CurrentColorInColor => write!(f, "currentColor must not be used within color"),
+            UnknownPropertyName(property_name) => {
+                write!(f, "unknown property name \"{}\"", property_name)
+            }
+            InvalidUnit(expected_unit) => write!(f, "invalid unit, expected {}", expected_unit),
+            OutOfRange => write!(f, "value out of range"),
+            InvalidColor(error) => write!(f, "invalid color: {}", error),
+            InvalidKeyword(expected_keywords) => {
+                write!(f, "invalid keyword, expected {}", expected_keywords)
+            }
+            NotASingleCharacter => write!(f, "expected a single character"),
+            NotAnInteger => write!(f, "expected an integer"),
+        }
+    }
 }
@@ -1,12 +1,20 @@
 use super::declaration::{
-    MapCssDeclaration, MapCssDeclarationProperty, MapCssDeclarationValueType,
+    BinaryOperator, Expression, ExpressionType, Function, Length, MapCssDeclaration,
+    MapCssDeclarationProperty, MapCssDeclarationValueType, MapCssRule, MapCssRules, UnaryOperator,
+    Value, RGBA,
 };
+use super::diagnostic::{Diagnostic, Severity};
 use super::error::MapCssError;
-use super::selectors::{Selector, SelectorCondition, SelectorType};
-use super::MapCssAcknowledgement;
+use super::selectors::{
+    PseudoClass, Selector, SelectorCondition, SelectorType, TagNumber, TagRegex,
+};
+use super::{MapCssAcknowledgement, MapCssParseError};
+use once_cell::sync::Lazy;
 use pest::iterators::{Pair, Pairs};
+use pest::pratt_parser::{Assoc, Op, PrattParser};
 use pest::Parser;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::rc::Rc;
 
 pub type FloatSize = f64;
@@ -16,120 +24,420 @@ pub type IntSize = i32;
 #[grammar = "grammar/mapcss.pest"]
 pub struct MapCssParser;
 
+/// Operators of `eval()` expressions, from the lowest to the highest precedence.
+static EXPRESSION_PARSER: Lazy<PrattParser<Rule>> = Lazy::new(|| {
+    PrattParser::new()
+        .op(Op::infix(Rule::or, Assoc::Left))
+        .op(Op::infix(Rule::and, Assoc::Left))
+        .op(Op::infix(Rule::equal, Assoc::Left)
+            | Op::infix(Rule::not_equal, Assoc::Left)
+            | Op::infix(Rule::less_equal, Assoc::Left)
+            | Op::infix(Rule::greater_equal, Assoc::Left)
+            | Op::infix(Rule::less, Assoc::Left)
+            | Op::infix(Rule::greater, Assoc::Left))
+        .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+        .op(Op::infix(Rule::multiply, Assoc::Left)
+            | Op::infix(Rule::divide, Assoc::Left)
+            | Op::infix(Rule::modulo, Assoc::Left))
+        .op(Op::prefix(Rule::negate) | Op::prefix(Rule::not))
+});
+
 impl MapCssParser {
-    // TODO: Use this method for testing against various stylesheets (maybe with errors?)
-    pub fn lex(mapcss: &str) -> Pairs<Rule> {
-        MapCssParser::parse(Rule::rule_list, mapcss).unwrap()
+    pub fn lex(mapcss: &str) -> Result<Pairs<'_, Rule>, Diagnostic> {
+        MapCssParser::parse(Rule::rule_list, mapcss).map_err(Diagnostic::from_syntax_error)
     }
 
+    /// Parses the stylesheet like browsers parse CSS: rules and declarations that are invalid or
+    /// unsupported are dropped, the usable rules are returned together with a warning for each
+    /// dropped part. Only unbalanced braces make the whole stylesheet unusable.
     pub fn parse_mapcss(
         mapcss: &str,
-    ) -> Result<
-        (
-            Option<MapCssAcknowledgement>,
-            HashMap<SelectorType, HashMap<SelectorCondition, Vec<MapCssDeclaration>>>,
-        ),
-        MapCssError,
-    > {
-        let pairs = MapCssParser::lex(mapcss);
-
-        let mut selector_to_declaration_list: HashMap<
-            SelectorType,
-            HashMap<SelectorCondition, Vec<MapCssDeclaration>>,
-        > = HashMap::new();
+    ) -> Result<(Option<MapCssAcknowledgement>, MapCssRules, Vec<Diagnostic>), Diagnostic> {
+        MapCssParser::parse_mapcss_from_file(mapcss, None)
+    }
+
+    /// Like [`MapCssParser::parse_mapcss`], but the diagnostics name the file the stylesheet
+    /// was read from.
+    pub fn parse_mapcss_from_file(
+        mapcss: &str,
+        file_name: Option<&str>,
+    ) -> Result<(Option<MapCssAcknowledgement>, MapCssRules, Vec<Diagnostic>), Diagnostic> {
+        let (parsed_rules, warnings) = MapCssParser::parse_rules(mapcss, file_name)?;
+
+        let mut selector_to_declaration_list: MapCssRules = HashMap::new();
         let mut acknowledgement = None;
+        // each selector of a selector list is an own rule, in the order of the stylesheet
+        let mut rule_count = 0;
+
+        for parsed_rule in parsed_rules {
+            let declarations: Vec<MapCssDeclaration> = parsed_rule
+                .declarations
+                .into_iter()
+                .map(|(declaration, _)| declaration)
+                .collect();
+
+            // handle meta information like the meta mapcss block
+            for selector in parsed_rule.selectors.into_iter() {
+                let selector_type = selector.selector_type();
+                if selector_type == SelectorType::Meta {
+                    // TODO: Bail semantic error (the meta block has unique MapCSS properties that may not appear in any other ruleset)
+                    acknowledgement =
+                        MapCssAcknowledgement::from_declarations(declarations.clone()).ok();
+                    break;
+                } else {
+                    selector_to_declaration_list
+                        .entry(selector_type)
+                        .or_default()
+                        .push(MapCssRule {
+                            index: rule_count,
+                            conditions: selector.conditions().clone(),
+                            declarations: declarations.clone(),
+                        });
+                    rule_count += 1;
+                }
+            }
+        }
+
+        Ok((acknowledgement, selector_to_declaration_list, warnings))
+    }
+
+    /// Parses the rules in the order of the stylesheet, keeping the location of each rule and
+    /// declaration, which tools like the linter need to point at them.
+    pub(crate) fn parse_rules<'i>(
+        mapcss: &'i str,
+        file_name: Option<&str>,
+    ) -> Result<(Vec<ParsedRule<'i>>, Vec<Diagnostic>), Diagnostic> {
+        let with_file_name = |diagnostic: Diagnostic| match file_name {
+            Some(file_name) => diagnostic.with_file_name(file_name),
+            None => diagnostic,
+        };
+
+        let pairs = MapCssParser::lex(mapcss).map_err(with_file_name)?;
+
+        let mut parsed_rules = Vec::new();
+        let mut warnings = Vec::new();
+
+        handle_rule_list(
+            pairs,
+            &SelectorCondition::True,
+            &mut parsed_rules,
+            &mut warnings,
+        );
+
+        Ok((
+            parsed_rules,
+            warnings.into_iter().map(with_file_name).collect(),
+        ))
+    }
+}
+
+/// A rule whose invalid selectors and declarations were dropped already.
+pub(crate) struct ParsedRule<'i> {
+    /// the selectors, whose conditions include the conditions of the surrounding blocks
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<(MapCssDeclaration, pest::Span<'i>)>,
+    pub span: pest::Span<'i>,
+}
+
+/// Adds the rules to the list, where the rules of @media and @supports blocks additionally need
+/// to fulfill the block condition.
+fn handle_rule_list<'i>(
+    pairs: Pairs<'i, Rule>,
+    block_condition: &SelectorCondition,
+    parsed_rules: &mut Vec<ParsedRule<'i>>,
+    warnings: &mut Vec<Diagnostic>,
+) {
+    for rule in pairs {
+        match rule.as_rule() {
+            Rule::COMMENT => {
+                // ignore comments for now
+            }
+            Rule::conditional_block => {
+                let mut block_contents = rule.into_inner();
+                let query = block_contents
+                    .find(|pair| pair.as_rule() == Rule::conditional_query)
+                    .unwrap();
+
+                // a block whose condition cannot be evaluated is dropped like an invalid rule
+                let condition = match handle_conditional_query(query) {
+                    Ok(condition) => and_conditions(block_condition.clone(), condition),
+                    Err(diagnostic) => {
+                        warnings.push(diagnostic);
+                        continue;
+                    }
+                };
 
-        for rule in pairs {
-            match rule.as_rule() {
-                Rule::COMMENT => {
-                    // ignore comments for now
+                // the rules can never apply
+                if condition == SelectorCondition::False {
+                    continue;
                 }
-                Rule::rule => {
-                    let rule_contents = rule.into_inner();
-                    let mut selector_list: Vec<Selector> = Vec::with_capacity(2);
-                    let mut declarations: Vec<MapCssDeclaration> = Vec::new();
-
-                    for rule_content in rule_contents {
-                        match rule_content.as_rule() {
-                            Rule::rule_selector => {
-                                selector_list.push(handle_selector(rule_content));
-                            }
-                            Rule::rule_declaration => match handle_declaration(rule_content) {
-                                Ok(dec) => {
-                                    declarations.push(dec);
+
+                handle_rule_list(block_contents, &condition, parsed_rules, warnings);
+            }
+            Rule::invalid_rule => warnings.push(Diagnostic::from_span(
+                Severity::Warning,
+                MapCssError::InvalidSyntax("rule").to_string(),
+                rule.as_span(),
+            )),
+            Rule::rule => {
+                let rule_span = rule.as_span();
+                let mut selector_list: Vec<(Selector, pest::Span<'i>)> = Vec::with_capacity(2);
+                let mut declarations = Vec::new();
+
+                for rule_content in rule.into_inner() {
+                    let span = rule_content.as_span();
+                    let mut warn = |error: MapCssError| {
+                        warnings.push(Diagnostic::from_span(
+                            Severity::Warning,
+                            error.to_string(),
+                            span,
+                        ))
+                    };
+
+                    match rule_content.as_rule() {
+                        Rule::rule_selector => match handle_selector(rule_content) {
+                            Ok(mut selector) => {
+                                let conditions = selector
+                                    .conditions()
+                                    .clone()
+                                    .add_condition(block_condition.clone())
+                                    .simplify();
+
+                                if conditions == SelectorCondition::False {
+                                    warn(MapCssError::UnsatisfiableSelector(
+                                        span.as_str().trim().to_owned(),
+                                    ));
+                                    continue;
                                 }
-                                Err(err) => {
-                                    eprintln!("{}", err);
+
+                                // the meta block describes the whole stylesheet
+                                if selector.selector_type() == SelectorType::Meta
+                                    && conditions != SelectorCondition::True
+                                {
+                                    warn(MapCssError::MetaSelectorWithConditions);
+                                    continue;
                                 }
-                            },
-                            Rule::COMMENT => (),
-                            _ => todo!("{rule_content}"),
-                        };
-                    }
 
-                    // handle meta information like the meta mapcss block
-                    debug_assert!(!selector_list.is_empty());
-                    let selector_list_len = selector_list.len();
-
-                    for selector in selector_list.into_iter() {
-                        let selector_type = selector.selector_type();
-                        if selector_type == SelectorType::Meta {
-                            // TODO: Bail semantic error (the meta block has unique MapCSS properties that may not appear in any other ruleset)
-                            debug_assert_eq!(selector_list_len, 1);
-
-                            if selector.conditions() == &SelectorCondition::True {
-                                acknowledgement =
-                                    MapCssAcknowledgement::from_declarations(declarations.clone())
-                                        .ok();
-                                break;
-                            } else {
-                                warn!("The meta {{}} block must not have any selector conditions, ignoring!");
+                                selector.set_conditions(conditions);
+                                selector_list.push((selector, span));
                             }
-                        } else {
-                            selector_to_declaration_list
-                                .entry(selector_type)
-                                .or_default()
-                                .entry(selector.conditions().clone())
-                                .or_default()
-                                .extend(declarations.clone());
+                            Err(err) => warn(err),
+                        },
+                        Rule::rule_declaration => match handle_declaration(rule_content) {
+                            Ok(dec) => {
+                                declarations.push((dec, span));
+                            }
+                            Err(err) => warn(err),
+                        },
+                        Rule::invalid_declaration => {
+                            warn(MapCssError::InvalidSyntax("declaration"))
                         }
-                    }
+                        Rule::COMMENT => (),
+                        _ => unreachable!(),
+                    };
                 }
-                Rule::EOI => break,
-                _ => unreachable!(),
+
+                // the properties of the meta block do not apply to any other selector
+                if selector_list.len() > 1 {
+                    selector_list.retain(|(selector, span)| {
+                        let is_meta = selector.selector_type() == SelectorType::Meta;
+
+                        if is_meta {
+                            warnings.push(Diagnostic::from_span(
+                                Severity::Warning,
+                                MapCssError::MetaSelectorInList.to_string(),
+                                *span,
+                            ));
+                        }
+
+                        !is_meta
+                    });
+                }
+
+                // all selectors of the rule were dropped
+                if selector_list.is_empty() {
+                    continue;
+                }
+
+                parsed_rules.push(ParsedRule {
+                    selectors: selector_list
+                        .into_iter()
+                        .map(|(selector, _)| selector)
+                        .collect(),
+                    declarations,
+                    span: rule_span,
+                });
+            }
+            Rule::EOI => break,
+            _ => unreachable!(),
+        };
+    }
+}
+
+/// Evaluates the condition of a @media or @supports block. Features known when parsing are
+/// resolved to `True` or `False`, whereas the zoom range is checked when rendering.
+/// Invalid zoom levels like `(min-zoom: abc)` are reported at the feature, as even their
+/// negation is meaningless.
+fn handle_conditional_query(query: Pair<'_, Rule>) -> Result<SelectorCondition, Diagnostic> {
+    let mut query_contents = without_comments(query.into_inner());
+    let mut condition = handle_conditional_term(query_contents.next().unwrap())?;
+
+    // the operators are evaluated from left to right, as CSS forbids mixing them
+    while let Some(operator) = query_contents.next() {
+        let term = handle_conditional_term(query_contents.next().unwrap())?;
+
+        condition = match operator.as_str() {
+            "and" => and_conditions(condition, term),
+            _ => or_conditions(condition, term),
+        };
+    }
+
+    Ok(condition)
+}
+
+fn handle_conditional_term(term: Pair<'_, Rule>) -> Result<SelectorCondition, Diagnostic> {
+    let mut term_contents = without_comments(term.into_inner());
+    let mut inner = term_contents.next().unwrap();
+
+    let is_negated = inner.as_rule() == Rule::conditional_not;
+    if is_negated {
+        inner = term_contents.next().unwrap();
+    }
+
+    let condition = match inner.as_rule() {
+        Rule::conditional_query => handle_conditional_query(inner)?,
+        Rule::conditional_feature => {
+            let feature_span = inner.as_span();
+            let mut feature = without_comments(inner.into_inner());
+            let name = feature.next().unwrap().as_str();
+            let value = feature.next().map(|value| value.as_str().trim());
+
+            handle_conditional_feature(name, value).map_err(|error| {
+                let error = MapCssError::InvalidBlockCondition {
+                    condition: feature_span.as_str().to_owned(),
+                    error,
+                };
+
+                Diagnostic::from_span(Severity::Warning, error.to_string(), feature_span)
+            })?
+        }
+        _ => unreachable!(),
+    };
+
+    Ok(if is_negated {
+        negate_condition(condition)
+    } else {
+        condition
+    })
+}
+
+fn handle_conditional_feature(
+    name: &str,
+    value: Option<&str>,
+) -> Result<SelectorCondition, MapCssParseError> {
+    let zoom_level = || {
+        value
+            .and_then(|value| value.parse::<i64>().ok())
+            .ok_or(MapCssParseError::NotAnInteger)
+            .and_then(|zoom_level| {
+                u8::try_from(zoom_level).map_err(|_| MapCssParseError::OutOfRange)
+            })
+    };
+    let to_condition = |is_met: bool| match is_met {
+        true => SelectorCondition::True,
+        false => SelectorCondition::False,
+    };
+
+    Ok(match (name, value) {
+        ("user-agent", Some(user_agent)) => to_condition(user_agent == "rosm"),
+        ("output-format", Some(format)) => to_condition(format == "png"),
+        ("min-zoom", _) => SelectorCondition::MinZoomLevel(zoom_level()?),
+        ("max-zoom", _) => SelectorCondition::MaxZoomLevel(zoom_level()?),
+        // a declaration like (text-halo-radius: 2) is supported if we are able to parse it,
+        // without a value only the property name needs to be known
+        (name, value) => {
+            let declaration = format!("{}: {};", name, value.unwrap_or("0"));
+
+            let Ok(mut pairs) = MapCssParser::parse(Rule::rule_declaration, &declaration) else {
+                return Ok(SelectorCondition::False);
             };
+
+            to_condition(match handle_declaration(pairs.next().unwrap()) {
+                Ok(_) => true,
+                Err(MapCssError::InvalidDeclarationValue { .. }) => value.is_none(),
+                Err(_) => false,
+            })
         }
+    })
+}
 
-        Ok((acknowledgement, selector_to_declaration_list))
+fn negate_condition(condition: SelectorCondition) -> SelectorCondition {
+    match condition {
+        SelectorCondition::True => SelectorCondition::False,
+        SelectorCondition::False => SelectorCondition::True,
+        condition => SelectorCondition::Not(Rc::new(Selector::new(SelectorType::Any, condition))),
     }
 }
 
-fn handle_selector(selectors: Pair<'_, Rule>) -> Selector {
+fn and_conditions(a: SelectorCondition, b: SelectorCondition) -> SelectorCondition {
+    if a == SelectorCondition::False || b == SelectorCondition::False {
+        return SelectorCondition::False;
+    }
+
+    a.add_condition(b)
+}
+
+fn or_conditions(a: SelectorCondition, b: SelectorCondition) -> SelectorCondition {
+    match (a, b) {
+        (SelectorCondition::True, _) | (_, SelectorCondition::True) => SelectorCondition::True,
+        (SelectorCondition::False, condition) | (condition, SelectorCondition::False) => condition,
+        // a or b = not (not a and not b)
+        (a, b) => negate_condition(and_conditions(negate_condition(a), negate_condition(b))),
+    }
+}
+
+pub(crate) fn handle_selector(selectors: Pair<'_, Rule>) -> Result<Selector, MapCssError> {
     let mut rule_selectors = selectors.into_inner();
 
-    let main_selector = rule_selectors.next().unwrap();
+    // `:not( /* comment */ way)` starts with a comment
+    let main_selector = rule_selectors
+        .find(|pair| pair.as_rule() == Rule::selector)
+        .unwrap();
 
     assert_eq!(main_selector.as_rule(), Rule::selector);
 
-    let mut main_selector = selector_span_to_type(
+    let mut selector = selector_span_to_type(
         main_selector.as_span().as_str(),
-        selector_condition_from_rule_selectors(&mut rule_selectors.clone()),
+        selector_condition_from_rule_selectors(&mut rule_selectors.clone())?,
     );
 
-    let mut main_selector_conditions = main_selector.clone().conditions().clone();
+    // `a > b > c` styles c, whose parent needs to match `a > b`
+    let mut descendant = rule_selectors.find(|x| x.as_rule() == Rule::rule_descendant);
 
-    for descendant_selectors in rule_selectors.filter(|x| x.as_rule() == Rule::rule_descendant) {
-        let descendant_selector =
-            handle_selector(descendant_selectors.into_inner().next().unwrap());
+    while let Some(descendant_selectors) = descendant {
+        let mut descendant_selectors = descendant_selectors.into_inner();
 
-        main_selector_conditions = main_selector_conditions.add_condition(
-            SelectorCondition::HasDescendant(Rc::new(descendant_selector)),
-        );
-    }
+        let link_conditions =
+            selector_condition_from_rule_selectors(&mut descendant_selectors.clone())?;
+
+        let mut child_selectors = descendant_selectors
+            .find(|x| x.as_rule() == Rule::rule_selector)
+            .unwrap()
+            .into_inner();
+        let child_selector = child_selectors.next().unwrap();
 
-    main_selector.set_conditions(main_selector_conditions);
+        // the parent comes first, like in the stylesheet
+        let child_conditions =
+            SelectorCondition::HasParent(Rc::new(selector), Box::new(link_conditions))
+                .add_condition(selector_condition_from_rule_selectors(
+                    &mut child_selectors.clone(),
+                )?);
 
-    main_selector
+        selector = selector_span_to_type(child_selector.as_span().as_str(), child_conditions);
+        descendant = child_selectors.find(|x| x.as_rule() == Rule::rule_descendant);
+    }
+
+    Ok(selector)
 }
 
 #[inline]
@@ -152,95 +460,155 @@ fn selector_span_to_type(span: &str, selector_conditions: SelectorCondition) ->
 
 fn selector_condition_from_rule_selectors(
     rules: &mut pest::iterators::Pairs<'_, Rule>,
-) -> SelectorCondition {
+) -> Result<SelectorCondition, MapCssError> {
     // fast path if we have no rules to extract
     if rules.peek().is_none() {
-        return SelectorCondition::True;
+        return Ok(SelectorCondition::True);
     }
 
     let mut selector_conditions = Vec::new();
 
     for rule in rules {
         match rule.as_rule() {
-            Rule::rule_descendant => {
+            // children and their link conditions are handled by handle_selector()
+            Rule::rule_descendant | Rule::rule_selector | Rule::COMMENT => {
                 continue;
             }
             Rule::selector_tests => {
-                let mut inner_rules = rule.into_inner().peekable();
+                let mut inner_rules = without_comments(rule.into_inner()).peekable();
 
                 let inner_rule = inner_rules.next();
 
-                let Some(inner_rule) = inner_rule else { continue; };
+                let Some(inner_rule) = inner_rule else {
+                    continue;
+                };
 
                 match inner_rule.as_rule() {
                     Rule::tag_value => {
                         let next_rule = inner_rules.peek();
 
                         match next_rule.map(|r| r.as_rule()) {
+                            Some(Rule::regex_comparison) => {
+                                let operator = inner_rules.next().unwrap().as_str();
+                                let mut regex = inner_rules.next().unwrap().into_inner();
+                                let pattern = regex.next().unwrap().as_str().replace("\\/", "/");
+                                let flags = regex.next().unwrap().as_str();
+
+                                let regex = TagRegex::new(&pattern, flags).map_err(|err| {
+                                    MapCssError::InvalidRegex {
+                                        pattern: pattern.clone(),
+                                        reason: err.to_string(),
+                                    }
+                                })?;
+
+                                let key = unquote_tag_value(inner_rule);
+
+                                selector_conditions.push(match operator {
+                                    "=~" => SelectorCondition::TagValueMatches(key, regex),
+                                    "!~" => SelectorCondition::TagValueNotMatches(key, regex),
+                                    _ => unreachable!(),
+                                });
+                            }
                             Some(Rule::comparison) => {
                                 let operator = inner_rules.next().unwrap().as_span().as_str();
 
-                                operator_to_condition(
+                                selector_conditions.push(operator_to_condition(
                                     operator,
                                     inner_rule,
                                     inner_rules
                                         .next()
                                         .expect("Target required when doing an comparison!"),
-                                    &mut selector_conditions,
-                                );
+                                )?);
                             }
-                            None => selector_conditions.push(SelectorCondition::HasTag(
-                                inner_rule.as_span().as_str().to_owned(),
-                            )),
-                            _ => unreachable!(),
+                            Some(Rule::selector_test_truthy) => selector_conditions.push(
+                                SelectorCondition::TagIsTruthy(unquote_tag_value(inner_rule)),
+                            ),
+                            Some(Rule::selector_test_falsy) => selector_conditions
+                                .push(SelectorCondition::TagIsFalsy(unquote_tag_value(inner_rule))),
+                            None => selector_conditions
+                                .push(SelectorCondition::HasTag(unquote_tag_value(inner_rule))),
+                            _ => return Err(MapCssError::InvalidSyntax("selector")),
+                        }
+                    }
+                    Rule::selector_test_not => {
+                        let key = unquote_tag_value(inner_rules.next().unwrap());
+
+                        selector_conditions.push(match inner_rules.next() {
+                            // [!key?]
+                            Some(_truthy) => SelectorCondition::Not(Rc::new(Selector::new(
+                                SelectorType::Any,
+                                SelectorCondition::TagIsTruthy(key),
+                            ))),
+                            None => SelectorCondition::HasNotTag(key),
+                        });
+                    }
+                    Rule::selector_test_class => {
+                        let mut inner = without_comments(inner_rule.into_inner());
+                        let class_or_negation = inner.next().unwrap();
+
+                        if class_or_negation.as_rule() == Rule::class_negation {
+                            let class = inner.next().unwrap().as_str().to_owned();
+
+                            selector_conditions.push(SelectorCondition::Not(Rc::new(
+                                Selector::new(
+                                    SelectorType::Any,
+                                    SelectorCondition::HasClass(class),
+                                ),
+                            )));
+                        } else {
+                            selector_conditions.push(SelectorCondition::HasClass(
+                                class_or_negation.as_str().to_owned(),
+                            ));
                         }
                     }
                     Rule::selector_test_zoom_level => {
                         let selector_test = inner_rule.into_inner().next().unwrap();
                         let span = selector_test.as_span().as_str();
 
-                        match selector_test.as_rule() {
+                        let invalid_zoom_level = |error| MapCssError::InvalidSelector {
+                            selector: span.to_owned(),
+                            error,
+                        };
+                        let parse_zoom_level = |zoom_level: &str| {
+                            zoom_level
+                                .parse::<u8>()
+                                .map_err(|_| invalid_zoom_level(MapCssParseError::OutOfRange))
+                        };
+
+                        selector_conditions.push(match selector_test.as_rule() {
+                            // "|z8"
                             Rule::selector_test_zoom_level_exact => {
-                                // "|z8"
-                                selector_conditions.push(SelectorCondition::ExactZoomLevel(
-                                    span[2..span.len()].parse::<u8>().unwrap(),
-                                ));
+                                SelectorCondition::ExactZoomLevel(parse_zoom_level(&span[2..])?)
                             }
                             // use rfind because the minus is always located more towards the end
                             Rule::selector_test_zoom_level_closed_range => {
                                 // "|z10-12"
                                 let minus_pos = span.rfind('-').unwrap();
 
-                                let min_level = &span[2..minus_pos].parse::<u8>().unwrap();
+                                let min_level = parse_zoom_level(&span[2..minus_pos])?;
                                 // skip the minus itself
-                                let max_level =
-                                    &span[minus_pos + 1..span.len()].parse::<u8>().unwrap();
+                                let max_level = parse_zoom_level(&span[minus_pos + 1..])?;
 
-                                // TODO: Throw error if it is invalid syntax
-                                assert!(max_level > min_level);
+                                if min_level > max_level {
+                                    return Err(invalid_zoom_level(MapCssParseError::OutOfRange));
+                                }
 
-                                selector_conditions.push(SelectorCondition::RangeZoomLevel(
-                                    *min_level, *max_level,
-                                ));
+                                SelectorCondition::RangeZoomLevel(min_level, max_level)
                             }
+                            // "|z14-" or "|z4-"
                             Rule::selector_test_zoom_level_open_right_range => {
-                                // "|z14-" or "|z4-"
-                                let zoom_level =
-                                    &span[2..span.rfind('-').unwrap()].parse::<u8>().unwrap();
-
-                                selector_conditions
-                                    .push(SelectorCondition::MinZoomLevel(*zoom_level));
+                                SelectorCondition::MinZoomLevel(parse_zoom_level(
+                                    &span[2..span.rfind('-').unwrap()],
+                                )?)
                             }
-                            _ => {
-                                dbg!(selector_test);
-                                todo!();
+                            // "|z-12"
+                            Rule::selector_test_zoom_level_open_left_range => {
+                                SelectorCondition::MaxZoomLevel(parse_zoom_level(&span[3..])?)
                             }
-                        }
-                    }
-                    _ => {
-                        dbg!(inner_rule);
-                        todo!();
+                            _ => unreachable!(),
+                        });
                     }
+                    _ => return Err(MapCssError::InvalidSyntax("selector")),
                 }
             }
             Rule::selector_pseudo_classes => {
@@ -250,84 +618,82 @@ fn selector_condition_from_rule_selectors(
                     .expect("A pseudo class needs a content for it!");
 
                 match pseudo_class.as_rule() {
-                    Rule::closed_pseudo_class => {
-                        selector_conditions.push(SelectorCondition::ClosedPath);
+                    Rule::pseudo_class => {
+                        let name = pseudo_class.into_inner().next().unwrap().as_str();
+
+                        selector_conditions.push(match name {
+                            "closed" => SelectorCondition::ClosedPath,
+                            // the extracts carry no edit history telling which elements changed
+                            "modified" => {
+                                return Err(MapCssError::UnsupportedPseudoClass(name.to_owned()))
+                            }
+                            _ => SelectorCondition::PseudoClass(
+                                PseudoClass::from_name(name).ok_or_else(|| {
+                                    MapCssError::UnknownPseudoClass(name.to_owned())
+                                })?,
+                            ),
+                        });
                     }
                     Rule::generic_pseudo_class => {
-                        let maybe_selector = pseudo_class.into_inner().next().unwrap();
+                        let maybe_selector =
+                            without_comments(pseudo_class.into_inner()).next().unwrap();
 
                         if maybe_selector.as_rule() == Rule::selector {
                             let selector = maybe_selector;
-                            let span_str = selector.as_span().as_str();
-
-                            // we can ignore the `*` subclass, as it does not make any difference
-                            if span_str != "*" {
-                                selector_conditions.push(SelectorCondition::GenericPseudoClass(
-                                    span_str.to_owned(),
-                                ));
-                            }
+                            // `::*` applies to all layers
+                            selector_conditions.push(SelectorCondition::GenericPseudoClass(
+                                selector.as_span().as_str().to_owned(),
+                            ));
                         } else {
-                            // TODO: Find out what it does in other implementations
                             selector_conditions.push(SelectorCondition::GenericPseudoClass(
-                                maybe_selector.as_span().as_str().to_owned(),
+                                unquote_tag_value(maybe_selector),
                             ));
                         }
                     }
                     Rule::not_pseudo_class => {
                         selector_conditions.push(SelectorCondition::Not(Rc::new(handle_selector(
                             pseudo_class,
-                        ))));
-                    }
-                    _ => {
-                        dbg!(pseudo_class);
-                        unreachable!();
+                        )?)));
                     }
+                    _ => return Err(MapCssError::InvalidSyntax("selector")),
                 }
             }
-            _ => {
-                dbg!(&rule, selector_conditions);
-                todo!();
-            }
+            _ => return Err(MapCssError::InvalidSyntax("selector")),
         }
     }
 
-    match selector_conditions.len() {
+    Ok(match selector_conditions.len() {
         0 => SelectorCondition::True,
         1 => selector_conditions.into_iter().next().unwrap(),
         _ => SelectorCondition::List(selector_conditions),
-    }
+    })
 }
 
 fn operator_to_condition(
     operator: &str,
     target: pest::iterators::Pair<'_, Rule>,
     expected: pest::iterators::Pair<'_, Rule>,
-    condition_list: &mut Vec<SelectorCondition>,
-) {
-    let target = target.as_span().as_str().to_owned();
-    let expected = expected.as_span().as_str().to_owned();
+) -> Result<SelectorCondition, MapCssError> {
+    let target = unquote_tag_value(target);
+    let expected = unquote_tag_value(expected);
 
-    condition_list.push(match operator {
+    Ok(match operator {
         "=" => SelectorCondition::HasExactTagValue(target, expected),
         "!=" => SelectorCondition::HasNotTagValue(target, expected),
         ">" | ">=" | "<" | "<=" => {
-            let numeric_expected_value = expected.parse::<isize>().ok();
-
-            match numeric_expected_value {
-                Some(numeric_value) => match operator {
-                    ">" => SelectorCondition::ValueGreaterThan(target, numeric_value),
-                    ">=" => SelectorCondition::ValueGreaterThanEqual(target, numeric_value),
-                    "<" => SelectorCondition::ValueLessThan(target, numeric_value),
-                    "<=" => SelectorCondition::ValueLessThanEqual(target, numeric_value),
-                    _ => unreachable!(),
-                },
-                None => return,
+            let Some(number) = TagNumber::parse(&expected) else {
+                return Err(MapCssError::NonNumericComparison(expected));
+            };
+
+            match operator {
+                ">" => SelectorCondition::ValueGreaterThan(target, number),
+                ">=" => SelectorCondition::ValueGreaterThanEqual(target, number),
+                "<" => SelectorCondition::ValueLessThan(target, number),
+                "<=" => SelectorCondition::ValueLessThanEqual(target, number),
+                _ => unreachable!(),
             }
         }
-        _ => {
-            dbg!(operator);
-            todo!();
-        }
+        _ => unreachable!(),
     })
 }
 
@@ -338,70 +704,124 @@ fn handle_declaration(
 
     let mut inner = declaration.into_inner();
 
+    if inner.peek().map(|pair| pair.as_rule()) == Some(Rule::set_instruction) {
+        return Ok(handle_set_instruction(inner.next().unwrap()));
+    }
+
     let declaration_name = inner.next().unwrap().as_span().as_str();
     let inner = inner.next().unwrap();
     let inner_rule = inner.as_rule();
 
-    macro_rules! to_string {
-        () => {
-            // remove quotations
-            if inner_rule == Rule::double_quoted_string || inner_rule == Rule::single_quoted_string
-            {
-                let as_str = inner.as_span().as_str();
-                MapCssDeclarationValueType::String(as_str[1..as_str.len() - 1].to_owned())
+    macro_rules! eval_or {
+        ($expression_type: expr, $value: expr) => {
+            if inner_rule == Rule::eval_expression {
+                MapCssDeclarationValueType::Eval(
+                    handle_expression(
+                        inner
+                            .clone()
+                            .into_inner()
+                            .find(|pair| pair.as_rule() == Rule::expression)
+                            .unwrap(),
+                    )?,
+                    $expression_type,
+                )
             } else {
-                MapCssDeclarationValueType::String(inner.as_span().as_str().to_owned())
+                $value
             }
         };
     }
 
+    macro_rules! to_string {
+        () => {
+            eval_or!(ExpressionType::String, {
+                if inner_rule == Rule::double_quoted_string
+                    || inner_rule == Rule::single_quoted_string
+                {
+                    MapCssDeclarationValueType::String(unquote(inner.as_span().as_str()))
+                } else {
+                    MapCssDeclarationValueType::String(inner.as_span().as_str().to_owned())
+                }
+            })
+        };
+    }
+
+    macro_rules! invalid_value {
+        ($error: expr) => {
+            return Err(MapCssError::InvalidDeclarationValue {
+                declaration_name: declaration_name.to_owned(),
+                error: $error,
+            })
+        };
+    }
+
     macro_rules! to_float {
         () => {
-            if inner_rule == Rule::float || inner_rule == Rule::int {
-                MapCssDeclarationValueType::Float(
-                    inner
-                        .as_span()
-                        .as_str()
-                        .to_owned()
-                        .parse::<FloatSize>()
-                        .unwrap(),
-                )
-            } else {
-                dbg!(declaration_name, inner_rule);
-                panic!("Invalid float AST!");
-            }
+            eval_or!(
+                ExpressionType::Float,
+                if inner_rule == Rule::length {
+                    invalid_value!(MapCssParseError::InvalidUnit("no unit"))
+                } else if inner_rule == Rule::float || inner_rule == Rule::int {
+                    MapCssDeclarationValueType::Float(
+                        inner.as_span().as_str().parse::<FloatSize>().unwrap(),
+                    )
+                } else {
+                    invalid_value!(MapCssParseError::InvalidUnit("a number"))
+                }
+            )
         };
     }
 
     macro_rules! to_int {
         () => {
-            // TODO: Catch error
-            MapCssDeclarationValueType::Integer(
-                inner.as_span().as_str().parse::<IntSize>().unwrap(),
+            eval_or!(
+                ExpressionType::Integer,
+                if inner_rule == Rule::length {
+                    invalid_value!(MapCssParseError::InvalidUnit("no unit"))
+                } else if inner_rule == Rule::int {
+                    match inner.as_span().as_str().parse::<IntSize>() {
+                        Ok(integer) => MapCssDeclarationValueType::Integer(integer),
+                        Err(_) => invalid_value!(MapCssParseError::OutOfRange),
+                    }
+                } else {
+                    invalid_value!(MapCssParseError::InvalidUnit("an integer"))
+                }
+            )
+        };
+    }
+
+    macro_rules! to_length {
+        () => {
+            eval_or!(
+                ExpressionType::Length,
+                match inner.as_span().as_str().parse::<Length>() {
+                    Ok(length) => MapCssDeclarationValueType::Length(length),
+                    Err(error) => invalid_value!(error),
+                }
             )
         };
     }
 
     macro_rules! to_bool {
         () => {
-            // TODO: Catch error
-            MapCssDeclarationValueType::Boolean(match inner.as_span().as_str() {
-                "true" | "1" => true,
-                "false" | "0" => false,
-                _ => panic!("Invalid boolean value"),
-            })
+            eval_or!(
+                ExpressionType::Boolean,
+                MapCssDeclarationValueType::Boolean(match inner.as_span().as_str() {
+                    "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => invalid_value!(MapCssParseError::InvalidKeyword("true or false")),
+                })
+            )
         };
     }
 
     macro_rules! to_color {
         () => {
-            // TODO: Catch error
-            MapCssDeclarationValueType::Color(
-                inner
-                    .as_span()
-                    .as_str()
-                    .parse::<crate::mapcss::declaration::RGBA>()
-                    .unwrap(),
+            eval_or!(
+                ExpressionType::Color,
+                match parse_color(inner.as_span().as_str()) {
+                    Ok(color) => MapCssDeclarationValueType::Color(color),
+                    Err(error) => invalid_value!(error),
+                }
             )
         };
     }
@@ -410,13 +830,11 @@ fn handle_declaration(
         () => {
             // TODO: Catch error
             MapCssDeclarationValueType::String(if inner_rule == Rule::url {
-                let url_string = inner.into_inner().as_str();
-                url_string[1..url_string.len() - 1].to_owned()
+                unquote(inner.into_inner().as_str())
             } else if inner_rule == Rule::double_quoted_string
                 || inner_rule == Rule::single_quoted_string
             {
-                let as_str = inner.as_span().as_str();
-                as_str[1..as_str.len() - 1].to_owned()
+                unquote(inner.as_span().as_str())
             } else {
                 inner.as_span().as_str().to_owned()
             })
@@ -424,7 +842,9 @@ fn handle_declaration(
     }
 
     use crate::mapcss::declaration::{
-        LinecapDeclarationVariant, LinejoinDeclarationVariant, TextPositionDeclarationVariant,
+        LinecapDeclarationVariant, LinejoinDeclarationVariant,
+        TextAnchorHorizontalDeclarationVariant, TextAnchorVerticalDeclarationVariant,
+        TextPositionDeclarationVariant,
     };
 
     Ok(match declaration_name.to_ascii_lowercase().as_str() {
@@ -439,16 +859,19 @@ fn handle_declaration(
             // TODO: Make sure that the syntax is right
             // TODO: Prevent DoS?!
 
+            let Ok(dashes) = inner
+                .as_span()
+                .as_str()
+                .split(',')
+                .map(|x| x.trim().parse::<IntSize>())
+                .collect::<Result<Vec<IntSize>, _>>()
+            else {
+                invalid_value!(MapCssParseError::InvalidUnit("a list of integers"));
+            };
+
             (
                 MapCssDeclarationProperty::Dashes,
-                MapCssDeclarationValueType::IntegerArray(
-                    inner
-                        .as_span()
-                        .as_str()
-                        .split(',')
-                        .map(|x| x.parse::<IntSize>().unwrap())
-                        .collect::<Vec<IntSize>>(),
-                ),
+                MapCssDeclarationValueType::IntegerArray(dashes),
             )
         }
 
@@ -460,18 +883,52 @@ fn handle_declaration(
                 match inner.as_span().as_str() {
                     "center" => TextPositionDeclarationVariant::Center,
                     "line" => TextPositionDeclarationVariant::Line,
-                    _ => panic!(),
+                    _ => invalid_value!(MapCssParseError::InvalidKeyword("center or line")),
                 },
             ),
         ),
+        "text-anchor-horizontal" => (
+            MapCssDeclarationProperty::TextAnchorHorizontal,
+            MapCssDeclarationValueType::TextAnchorHorizontalDeclarationVariant(
+                match inner.as_span().as_str() {
+                    "left" => TextAnchorHorizontalDeclarationVariant::Left,
+                    "center" => TextAnchorHorizontalDeclarationVariant::Center,
+                    "right" => TextAnchorHorizontalDeclarationVariant::Right,
+                    _ => invalid_value!(MapCssParseError::InvalidKeyword("left, center or right")),
+                },
+            ),
+        ),
+        "text-anchor-vertical" => (
+            MapCssDeclarationProperty::TextAnchorVertical,
+            MapCssDeclarationValueType::TextAnchorVerticalDeclarationVariant(
+                match inner.as_span().as_str() {
+                    "above" => TextAnchorVerticalDeclarationVariant::Above,
+                    "center" => TextAnchorVerticalDeclarationVariant::Center,
+                    "below" => TextAnchorVerticalDeclarationVariant::Below,
+                    _ => invalid_value!(MapCssParseError::InvalidKeyword("above, center or below")),
+                },
+            ),
+        ),
+        "text-offset-y" => (MapCssDeclarationProperty::TextOffsetY, to_float!()),
         "text-spacing" => (MapCssDeclarationProperty::TextSpacing, to_int!()),
         "text-halo-color" => (MapCssDeclarationProperty::TextHaloColor, to_color!()),
         "text-halo-radius" => (MapCssDeclarationProperty::TextHaloRadius, to_int!()),
         "text-wrap-width" => (MapCssDeclarationProperty::TextWrapWidth, to_int!()),
+        "text-wrap-character" => {
+            let value = to_string!();
+
+            // the result of an eval() is only known per element
+            if let MapCssDeclarationValueType::String(character) = &value {
+                if character.chars().count() != 1 {
+                    invalid_value!(MapCssParseError::NotASingleCharacter);
+                }
+            }
+
+            (MapCssDeclarationProperty::TextWrapCharacter, value)
+        }
 
         "color" => (MapCssDeclarationProperty::Color, to_color!()),
-        // in MapCSS, the font size is always given in absolute pixels
-        "font-size" => (MapCssDeclarationProperty::FontSize, to_int!()),
+        "font-size" => (MapCssDeclarationProperty::FontSize, to_length!()),
         "font-color" => (MapCssDeclarationProperty::FontColor, to_color!()),
         "font-family" => (MapCssDeclarationProperty::FontFamily, to_string!()),
 
@@ -481,7 +938,7 @@ fn handle_declaration(
                 "none" => LinecapDeclarationVariant::None,
                 "round" => LinecapDeclarationVariant::Round,
                 "square" => LinecapDeclarationVariant::Square,
-                _ => panic!(),
+                _ => invalid_value!(MapCssParseError::InvalidKeyword("none, round or square")),
             }),
         ),
 
@@ -492,7 +949,7 @@ fn handle_declaration(
                     "round" => LinejoinDeclarationVariant::Round,
                     "miter" => LinejoinDeclarationVariant::Miter,
                     "bevel" => LinejoinDeclarationVariant::Bevel,
-                    _ => panic!(),
+                    _ => invalid_value!(MapCssParseError::InvalidKeyword("round, miter or bevel")),
                 },
             ),
         ),
@@ -509,10 +966,15 @@ fn handle_declaration(
         ),
 
         "opacity" => (MapCssDeclarationProperty::Opacity, to_float!()),
-        "width" => (MapCssDeclarationProperty::Width, to_float!()),
+        "width" => (MapCssDeclarationProperty::Width, to_length!()),
 
         "z-index" => (MapCssDeclarationProperty::ZIndex, to_float!()),
 
+        "hillshade-opacity" => (MapCssDeclarationProperty::HillshadeOpacity, to_float!()),
+        "hillshade-azimuth" => (MapCssDeclarationProperty::HillshadeAzimuth, to_float!()),
+        "hillshade-altitude" => (MapCssDeclarationProperty::HillshadeAltitude, to_float!()),
+        "contour-interval" => (MapCssDeclarationProperty::ContourInterval, to_float!()),
+
         _ => {
             return Err(MapCssError::UnknownDeclarationName(
                 declaration_name.to_owned(),
@@ -520,3 +982,573 @@ fn handle_declaration(
         }
     })
 }
+
+fn parse_color(color: &str) -> Result<RGBA, MapCssParseError> {
+    if color.eq_ignore_ascii_case("currentcolor") {
+        return Err(MapCssParseError::CurrentColorInColor);
+    }
+
+    color
+        .parse::<RGBA>()
+        .map_err(MapCssParseError::InvalidColor)
+}
+
+/// Returns the key or value held by a `tag_value`, removing the quotations of quoted ones.
+/// Skips the comments, which may appear between any two tokens of selectors, @media queries and
+/// expressions.
+fn without_comments<'i>(pairs: Pairs<'i, Rule>) -> impl Iterator<Item = Pair<'i, Rule>> {
+    pairs.filter(|pair| pair.as_rule() != Rule::COMMENT)
+}
+
+fn unquote_tag_value(tag_value: Pair<'_, Rule>) -> String {
+    debug_assert_eq!(tag_value.as_rule(), Rule::tag_value);
+
+    let inner = tag_value.into_inner().next().unwrap();
+
+    match inner.as_rule() {
+        Rule::unquoted_tag_value => inner.as_str().to_owned(),
+        _ => unquote(inner.as_str()),
+    }
+}
+
+/// Wraps the string into double quotes, escaping the characters resolved by [`unquote`].
+pub fn quote(string: &str) -> String {
+    let mut quoted_string = String::with_capacity(string.len() + 2);
+    quoted_string.push('"');
+
+    for char in string.chars() {
+        match char {
+            '"' | '\\' => {
+                quoted_string.push('\\');
+                quoted_string.push(char);
+            }
+            '\n' => quoted_string.push_str("\\n"),
+            '\t' => quoted_string.push_str("\\t"),
+            _ => quoted_string.push(char),
+        }
+    }
+
+    quoted_string.push('"');
+    quoted_string
+}
+
+/// Quotes a tag key or value unless it can be written without quotes, like `highway`.
+pub fn quote_tag_value(tag_value: &str) -> String {
+    let is_unquoted_tag_value = !tag_value.is_empty()
+        && tag_value
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '_' | '-' | ':' | '.'));
+
+    if is_unquoted_tag_value {
+        tag_value.to_owned()
+    } else {
+        quote(tag_value)
+    }
+}
+
+/// Removes the quotations of a single or double quoted string and resolves its escape sequences.
+pub fn unquote(quoted_string: &str) -> String {
+    let mut unquoted_string = String::with_capacity(quoted_string.len());
+    let mut chars = quoted_string[1..quoted_string.len() - 1].chars();
+
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            unquoted_string.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unquoted_string.push('\n'),
+            Some('t') => unquoted_string.push('\t'),
+            // e.g. \" or \\, a trailing backslash cannot occur as it would escape the quote
+            Some(escaped_char) => unquoted_string.push(escaped_char),
+            None => (),
+        }
+    }
+
+    unquoted_string
+}
+
+fn handle_set_instruction(set_instruction: Pair<'_, Rule>) -> MapCssDeclaration {
+    // skip the `set` keyword
+    let target = set_instruction.into_inner().nth(1).unwrap();
+
+    match target.as_rule() {
+        Rule::set_class => (
+            MapCssDeclarationProperty::SetClass,
+            MapCssDeclarationValueType::String(
+                target.into_inner().next().unwrap().as_str().to_owned(),
+            ),
+        ),
+        Rule::set_tag => {
+            let mut inner = target.into_inner();
+            let key = unquote_tag_value(inner.next().unwrap());
+
+            // `set key;` is a shorthand for `set key=yes;`
+            let value = inner
+                .next()
+                .map(unquote_tag_value)
+                .unwrap_or_else(|| "yes".to_owned());
+
+            (
+                MapCssDeclarationProperty::SetTag,
+                MapCssDeclarationValueType::Tag(key, value),
+            )
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn handle_expression(expression: Pair<'_, Rule>) -> Result<Expression, MapCssError> {
+    assert_eq!(expression.as_rule(), Rule::expression);
+
+    EXPRESSION_PARSER
+        .map_primary(|primary| {
+            Ok(match primary.as_rule() {
+                Rule::expression => handle_expression(primary)?,
+                Rule::expression_number => Expression::Literal(Value::Number(
+                    primary.as_str().parse::<FloatSize>().unwrap(),
+                )),
+                Rule::boolean => Expression::Literal(Value::Boolean(primary.as_str() == "true")),
+                Rule::double_quoted_string | Rule::single_quoted_string => {
+                    Expression::Literal(Value::String(unquote(primary.as_str())))
+                }
+                Rule::expression_function_call => {
+                    let mut inner = primary.into_inner();
+                    let function_name = inner.next().unwrap().as_str();
+
+                    let function = Function::from_name(&function_name.to_ascii_lowercase())
+                        .ok_or_else(|| {
+                            MapCssError::UnknownFunctionName(function_name.to_owned())
+                        })?;
+
+                    let arguments = without_comments(inner)
+                        .map(handle_expression)
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    if !function.accepts_argument_count(arguments.len()) {
+                        return Err(MapCssError::InvalidArgumentCount {
+                            function_name: function.name(),
+                            argument_count: arguments.len(),
+                        });
+                    }
+
+                    Expression::FunctionCall(function, arguments)
+                }
+                _ => unreachable!(),
+            })
+        })
+        .map_prefix(|operator, operand| {
+            let operator = match operator.as_rule() {
+                Rule::negate => UnaryOperator::Negate,
+                Rule::not => UnaryOperator::Not,
+                _ => unreachable!(),
+            };
+
+            Ok(Expression::UnaryOperation(operator, Box::new(operand?)))
+        })
+        .map_infix(|left, operator, right| {
+            let operator = match operator.as_rule() {
+                Rule::or => BinaryOperator::Or,
+                Rule::and => BinaryOperator::And,
+                Rule::equal => BinaryOperator::Equal,
+                Rule::not_equal => BinaryOperator::NotEqual,
+                Rule::less_equal => BinaryOperator::LessEqual,
+                Rule::greater_equal => BinaryOperator::GreaterEqual,
+                Rule::less => BinaryOperator::Less,
+                Rule::greater => BinaryOperator::Greater,
+                Rule::add => BinaryOperator::Add,
+                Rule::subtract => BinaryOperator::Subtract,
+                Rule::multiply => BinaryOperator::Multiply,
+                Rule::divide => BinaryOperator::Divide,
+                Rule::modulo => BinaryOperator::Modulo,
+                _ => unreachable!(),
+            };
+
+            Ok(Expression::BinaryOperation(
+                operator,
+                Box::new(left?),
+                Box::new(right?),
+            ))
+        })
+        // the Pratt parser only expects operands and operators
+        .parse(without_comments(expression.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unquote, MapCssParser};
+    use crate::mapcss::declaration::{MapCssDeclaration, MapCssRules};
+    use crate::mapcss::selectors::{SelectorCondition, SelectorType};
+
+    fn has_rule(
+        rules: &MapCssRules,
+        selector_type: SelectorType,
+        conditions: &SelectorCondition,
+    ) -> bool {
+        rules[&selector_type]
+            .iter()
+            .any(|rule| &rule.conditions == conditions)
+    }
+
+    /// The declarations of all rules with the given selector, in the order of the stylesheet.
+    fn declarations_of(
+        rules: &MapCssRules,
+        selector_type: SelectorType,
+        conditions: &SelectorCondition,
+    ) -> Vec<MapCssDeclaration> {
+        rules[&selector_type]
+            .iter()
+            .filter(|rule| &rule.conditions == conditions)
+            .flat_map(|rule| rule.declarations.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote(r#""Vaduz Mitte""#), "Vaduz Mitte");
+        assert_eq!(unquote(r#"'24/7'"#), "24/7");
+        assert_eq!(unquote(r#""say \"hi\"""#), r#"say "hi""#);
+        assert_eq!(unquote(r#"'it\'s'"#), "it's");
+        assert_eq!(unquote(r#""back\\slash\nline""#), "back\\slash\nline");
+    }
+
+    #[test]
+    fn test_quoted_and_unicode_tag_selectors() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            way[name="Vaduz Mitte"] { width: 1; }
+            way['opening_hours'!="24/7"] { width: 2; }
+            way[name=Zürich.Nord] { width: 3; }
+            way["addr:street"] { width: 4; }
+            way[name="say \"hi\""] { width: 5; }
+            "#,
+        )
+        .unwrap();
+
+        for condition in [
+            SelectorCondition::HasExactTagValue("name".into(), "Vaduz Mitte".into()),
+            SelectorCondition::HasNotTagValue("opening_hours".into(), "24/7".into()),
+            SelectorCondition::HasExactTagValue("name".into(), "Zürich.Nord".into()),
+            SelectorCondition::HasTag("addr:street".into()),
+            SelectorCondition::HasExactTagValue("name".into(), r#"say "hi""#.into()),
+        ] {
+            assert!(
+                has_rule(&rules, SelectorType::Way, &condition),
+                "{:?} is missing",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn test_comments_within_selectors() {
+        let parse = |selector: &str| {
+            let (_, rules, warnings) =
+                MapCssParser::parse_mapcss(&format!("{} {{ width: 1; }}", selector)).unwrap();
+
+            assert!(warnings.is_empty(), "{}: {:?}", selector, warnings);
+
+            rules
+                .into_iter()
+                .map(|(selector_type, rules)| (selector_type, rules[0].conditions.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        for (selector, commented_selector) in [
+            ("way[highway]", "way /* c */ [highway]"),
+            ("way[highway] > node", "way[highway] /* c */ > /* c */ node"),
+            (
+                "relation > [role=outer] way",
+                "relation > /* c */ [role=outer] /* c */ way",
+            ),
+            (
+                "way[highway=primary]",
+                "way[ /* c */ highway /* c */ = /* c */ primary /* c */ ]",
+            ),
+            ("way[name=~/^B/i]", "way[name /* c */ =~ /* c */ /^B/i]"),
+            (
+                "way[!bridge?]",
+                "way[ /* c */ ! /* c */ bridge /* c */ ? /* c */ ]",
+            ),
+            ("way!.minor", "way /* c */ ! /* c */ .minor"),
+            ("way:closed|z12-", "way /* c */ :closed /* c */ |z12-"),
+            ("way::casing", "way /* c */ :: /* c */ casing"),
+            (
+                "way:not(node[a])",
+                "way:not( /* c */ node /* c */ [a] /* c */ )",
+            ),
+        ] {
+            assert_eq!(
+                parse(commented_selector),
+                parse(selector),
+                "{}",
+                commented_selector
+            );
+        }
+    }
+
+    #[test]
+    fn test_color_declarations() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "way { color: rgba(0, 0, 255, 0.5); fill-color: darkolivegreen; text-color: hsl(120, 100%, 25%); }
+             node { color: notacolor; fill-color: currentColor; text-color: rgb(300, 0, 0); }",
+        )
+        .unwrap();
+
+        let declarations = &declarations_of(&rules, SelectorType::Way, &SelectorCondition::True);
+
+        assert_eq!(
+            declarations
+                .iter()
+                .map(|(_, value)| value.to_string())
+                .collect::<Vec<_>>(),
+            ["#0000ff80", "#556b2f", "#008000"]
+        );
+
+        // invalid colors are dropped instead of panicking
+        assert!(declarations_of(&rules, SelectorType::Node, &SelectorCondition::True).is_empty());
+    }
+
+    #[test]
+    fn test_label_declarations() {
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(
+            r#"
+            node { text-anchor-horizontal: right; text-anchor-vertical: above; text-offset-y: -2; }
+            way { text-wrap-character: ";"; text-wrap-character: eval(tag("separator")); }
+            area { text-wrap-character: "; "; text-anchor-vertical: middle; }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Node, &SelectorCondition::True)
+                .iter()
+                .map(|(_, value)| value.to_string())
+                .collect::<Vec<_>>(),
+            ["right", "above", "-2"]
+        );
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Way, &SelectorCondition::True).len(),
+            2
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| warning.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Dropped declaration text-wrap-character with invalid value: expected a single character.",
+                "Dropped declaration text-anchor-vertical with invalid value: invalid keyword, expected above, center or below.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditional_blocks() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            @supports (user-agent: josm) { way { width: 9; } }
+            @media (user-agent: rosm) and (output-format: png) { way { color: #ff0000; } }
+            @supports (min-josm-version: 9789) or (text-halo-radius: 2) {
+                node { text: "supported"; }
+                @supports not (unknown-property) { area { width: 2; } }
+                @supports (text-position: diagonal) { area { width: 3; } }
+            }
+            @media (min-zoom: 12) and (max-zoom: 14) { line { width: 1; } }
+            @media /* c */ not /* c */ ( /* c */ user-agent /* c */ : josm) { relation { width: 1; } }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(rules[&SelectorType::Way].len(), 1);
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Way, &SelectorCondition::True).len(),
+            1
+        );
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Node, &SelectorCondition::True).len(),
+            1
+        );
+        assert_eq!(rules[&SelectorType::Area].len(), 1);
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Area, &SelectorCondition::True).len(),
+            1
+        );
+        assert!(has_rule(
+            &rules,
+            SelectorType::Line,
+            &SelectorCondition::RangeZoomLevel(12, 14)
+        ));
+        assert!(has_rule(
+            &rules,
+            SelectorType::Relation,
+            &SelectorCondition::True
+        ));
+    }
+
+    #[test]
+    fn test_invalid_zoom_levels_drop_the_block() {
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(
+            r#"
+            @media (min-zoom: abc) { way { width: 1; } }
+            @media not (max-zoom: 300) { way { width: 2; } }
+            @media (min-zoom) { way { width: 3; } }
+            @media (min-zoom: 12) { @media not (max-zoom: -1) { way { width: 4; } } }
+            "#,
+        )
+        .unwrap();
+
+        assert!(!rules.contains_key(&SelectorType::Way));
+
+        let messages: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    2,
+                    "Dropped block with invalid condition (min-zoom: abc): expected an integer."
+                ),
+                (
+                    3,
+                    "Dropped block with invalid condition (max-zoom: 300): value out of range."
+                ),
+                (
+                    4,
+                    "Dropped block with invalid condition (min-zoom): expected an integer."
+                ),
+                (
+                    5,
+                    "Dropped block with invalid condition (max-zoom: -1): value out of range."
+                ),
+            ]
+        );
+        // the diagnostic points at the invalid feature, not at the whole query
+        assert_eq!(warnings[1].column, 24);
+    }
+
+    #[test]
+    fn test_invalid_values_are_dropped() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            r#"
+            way|z12-10 { width: 1; }
+            way|z12-12 { width: 1; }
+            node { z-index: 99999999999999999999; text-position: diagonal; width: 2; }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(rules[&SelectorType::Way].len(), 1);
+        assert!(has_rule(
+            &rules,
+            SelectorType::Way,
+            &SelectorCondition::ExactZoomLevel(12)
+        ));
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Node, &SelectorCondition::True).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_meta_selector_in_list_is_dropped() {
+        let (acknowledgement, rules, warnings) =
+            MapCssParser::parse_mapcss("meta, way { title: \"x\"; width: 1; }").unwrap();
+
+        assert!(acknowledgement.is_none());
+        assert!(!rules.contains_key(&SelectorType::Meta));
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Way, &SelectorCondition::True).len(),
+            2
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].message,
+            "Dropped meta selector, which must not share its block with other selectors."
+        );
+        assert_eq!(warnings[0].column, 1);
+    }
+
+    #[test]
+    fn test_meta_selector_with_conditions_is_dropped() {
+        let (acknowledgement, _, warnings) = MapCssParser::parse_mapcss(
+            "meta[foo] { title: \"x\"; }\n@media (min-zoom: 12) { meta { title: \"y\"; } }",
+        )
+        .unwrap();
+
+        assert!(acknowledgement.is_none());
+
+        let locations: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.line, warning.column, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                (
+                    1,
+                    1,
+                    "Dropped meta selector, which must not have any conditions."
+                ),
+                (
+                    2,
+                    25,
+                    "Dropped meta selector, which must not have any conditions."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recover_from_syntax_errors() {
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(
+            r#"
+            way { color: red; width: 1 2; text: "}"; }
+            way::* > foo[bar] { width: 2; }
+            @import url("other.mapcss");
+            @media (min-zoom: 12) {
+                node { width: 3 }
+                node|z13 ; { nested { width: 4; } }
+            }
+            node { z-index: 1; }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Way, &SelectorCondition::True).len(),
+            2
+        );
+        assert_eq!(
+            declarations_of(
+                &rules,
+                SelectorType::Node,
+                &SelectorCondition::MinZoomLevel(12)
+            )
+            .len(),
+            1
+        );
+        assert_eq!(
+            declarations_of(&rules, SelectorType::Node, &SelectorCondition::True).len(),
+            1
+        );
+
+        let messages: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "Dropped declaration with invalid syntax."),
+                (3, "Dropped rule with invalid syntax."),
+                (4, "Dropped rule with invalid syntax."),
+                (7, "Dropped rule with invalid syntax."),
+                (7, "Dropped rule with invalid syntax."),
+            ]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use super::declaration::MapCssRules;
+use super::declaration::{
+    MapCssDeclaration, MapCssDeclarationProperty, MapCssDeclarationValueType,
+};
+use super::diagnostic::Diagnostic;
+use super::parser::{handle_selector, quote, quote_tag_value, MapCssParser, Rule};
+use super::selectors::Selector;
+use super::MapCssAcknowledgement;
+use pest::iterators::Pair;
+use std::fmt::Write;
+
+const INDENTATION: &str = "    ";
+
+/// Writes parsed rules as a stylesheet, which parses to the same rules again. Each selector of a
+/// selector list is written as an own rule, @media and @supports blocks are already resolved into
+/// the selectors.
+pub fn print_rules(acknowledgement: Option<&MapCssAcknowledgement>, rules: &MapCssRules) -> String {
+    let mut stylesheet = String::new();
+
+    if let Some(acknowledgement) = acknowledgement {
+        let meta_declarations = [
+            (MapCssDeclarationProperty::Title, &acknowledgement.title),
+            (MapCssDeclarationProperty::Version, &acknowledgement.version),
+            (
+                MapCssDeclarationProperty::Description,
+                &acknowledgement.description,
+            ),
+            (
+                MapCssDeclarationProperty::Acknowledgement,
+                &acknowledgement.acknowledgement,
+            ),
+        ]
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(property, value)| {
+            print_declaration(&(
+                *property,
+                MapCssDeclarationValueType::String(value.to_string()),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+        write_rule(
+            &mut stylesheet,
+            "",
+            &["meta".to_owned()],
+            &meta_declarations,
+        );
+    }
+
+    let mut rules_in_order = rules
+        .iter()
+        .flat_map(|(selector_type, rules)| rules.iter().map(move |rule| (selector_type, rule)))
+        .collect::<Vec<_>>();
+    rules_in_order.sort_by_key(|(_, rule)| rule.index);
+
+    for (selector_type, rule) in rules_in_order {
+        if !stylesheet.is_empty() {
+            stylesheet.push('\n');
+        }
+
+        write_rule(
+            &mut stylesheet,
+            "",
+            &[Selector::new(*selector_type, rule.conditions.clone()).to_string()],
+            &rule
+                .declarations
+                .iter()
+                .map(print_declaration)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    stylesheet
+}
+
+/// Writes the declaration as MapCSS, e.g. `fill-color: #ff0000;` or `set .bridge;`.
+pub fn print_declaration((property, value): &MapCssDeclaration) -> String {
+    use MapCssDeclarationValueType::*;
+
+    match (property, value) {
+        (MapCssDeclarationProperty::SetClass, String(class)) => format!("set .{};", class),
+        (MapCssDeclarationProperty::SetTag, Tag(key, value)) => {
+            format!("set {}={};", quote_tag_value(key), quote_tag_value(value))
+        }
+        (property, value) => format!(
+            "{}: {};",
+            property,
+            match value {
+                String(string) => quote(string),
+                IntegerArray(integers) => integers
+                    .iter()
+                    .map(|integer| integer.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                value => value.to_string(),
+            }
+        ),
+    }
+}
+
+/// Formats the stylesheet consistently: one selector per line, declarations indented by four
+/// spaces and a blank line between rules. Unlike [`print_rules`], comments, the order of the
+/// rules, @media and @supports blocks as well as the values of declarations are kept as they
+/// are written, only selectors are normalized. Selectors and queries containing comments are kept
+/// as they are written, too.
+pub fn format_mapcss(mapcss: &str) -> Result<String, Diagnostic> {
+    let mut stylesheet = String::new();
+
+    format_rule_list(&mut stylesheet, MapCssParser::lex(mapcss)?, 0);
+
+    Ok(stylesheet)
+}
+
+fn format_rule_list<'i>(
+    stylesheet: &mut String,
+    pairs: impl Iterator<Item = Pair<'i, Rule>>,
+    depth: usize,
+) {
+    let indentation = INDENTATION.repeat(depth);
+    // comments stick to the rule following them
+    let mut follows_comment = true;
+
+    for pair in pairs {
+        if pair.as_rule() == Rule::EOI {
+            break;
+        }
+
+        if !follows_comment {
+            stylesheet.push('\n');
+        }
+        follows_comment = pair.as_rule() == Rule::COMMENT;
+
+        match pair.as_rule() {
+            Rule::conditional_block => {
+                let block = pair.as_str();
+                let (head, block_contents, brace_offset) = split_at_brace(pair);
+
+                let condition = if head.iter().any(contains_comment) {
+                    text_before_brace(block, brace_offset)
+                } else {
+                    head.iter()
+                        .flat_map(|pair| pair.as_str().split_whitespace())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+
+                writeln!(
+                    stylesheet,
+                    "{}{}{}{{",
+                    indentation,
+                    condition,
+                    brace_separator(&condition, &indentation)
+                )
+                .unwrap();
+                format_rule_list(stylesheet, block_contents.into_iter(), depth + 1);
+                writeln!(stylesheet, "{}}}", indentation).unwrap();
+            }
+            Rule::rule => format_rule(stylesheet, pair, &indentation),
+            // keep what we do not understand as it is
+            Rule::COMMENT | Rule::invalid_rule => {
+                writeln!(stylesheet, "{}{}", indentation, pair.as_str().trim()).unwrap()
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn format_rule(stylesheet: &mut String, rule: Pair<'_, Rule>, indentation: &str) {
+    let rule_text = rule.as_str();
+    let (head, rule_contents, brace_offset) = split_at_brace(rule);
+
+    let selectors = if head.iter().any(contains_comment) {
+        vec![text_before_brace(rule_text, brace_offset)]
+    } else {
+        head.into_iter()
+            .map(
+                |rule_selector| match handle_selector(rule_selector.clone()) {
+                    Ok(selector) => selector.to_string(),
+                    Err(_) => rule_selector.as_str().trim().to_owned(),
+                },
+            )
+            .collect()
+    };
+
+    let mut body = Vec::new();
+
+    for rule_content in rule_contents {
+        match rule_content.as_rule() {
+            Rule::rule_declaration => body.push(format_declaration(rule_content)),
+            Rule::invalid_declaration | Rule::COMMENT => {
+                body.push(rule_content.as_str().trim().to_owned())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    write_rule(stylesheet, indentation, &selectors, &body);
+}
+
+/// Splits the contents of a rule or block into the ones before its opening brace, like the
+/// selectors, and the ones after it. Only whitespace, commas and the brace lie between them, so
+/// the brace is the first one found in between, its offset within the rule or block is returned
+/// as well.
+fn split_at_brace(pair: Pair<'_, Rule>) -> (Vec<Pair<'_, Rule>>, Vec<Pair<'_, Rule>>, usize) {
+    let text = pair.as_str();
+    let start = pair.as_span().start();
+    let mut head = Vec::new();
+    let mut contents = Vec::new();
+    let mut brace_offset = None;
+    let mut previous_end = 0;
+
+    for content in pair.into_inner() {
+        let content_start = content.as_span().start() - start;
+
+        if brace_offset.is_none() {
+            brace_offset = text[previous_end..content_start]
+                .find('{')
+                .map(|offset| previous_end + offset);
+        }
+        previous_end = content.as_span().end() - start;
+
+        match brace_offset {
+            Some(_) => contents.push(content),
+            None => head.push(content),
+        }
+    }
+
+    let brace_offset =
+        brace_offset.unwrap_or_else(|| previous_end + text[previous_end..].find('{').unwrap());
+
+    (head, contents, brace_offset)
+}
+
+fn contains_comment(pair: &Pair<'_, Rule>) -> bool {
+    pair.as_rule() == Rule::COMMENT
+        || pair
+            .clone()
+            .into_inner()
+            .flatten()
+            .any(|pair| pair.as_rule() == Rule::COMMENT)
+}
+
+/// The text of a rule or block before its opening brace as it is written. If it ends with a line
+/// comment, so does the text, as the brace needs to go onto the next line.
+fn text_before_brace(text: &str, brace_offset: usize) -> String {
+    let head = text[..brace_offset].trim_end_matches([' ', '\t']);
+
+    match head.ends_with('\n') {
+        true => format!("{}\n", head.trim()),
+        false => head.trim().to_owned(),
+    }
+}
+
+/// Separates the text before an opening brace from it, which is on the next line after a line
+/// comment.
+fn brace_separator<'a>(text: &str, indentation: &'a str) -> &'a str {
+    match text.ends_with('\n') {
+        true => indentation,
+        false => " ",
+    }
+}
+
+fn format_declaration(declaration: Pair<'_, Rule>) -> String {
+    let mut inner = declaration.into_inner();
+    let mut comments = Vec::new();
+
+    let declaration = match inner.next().unwrap() {
+        // skip the `set` keyword
+        set_instruction if set_instruction.as_rule() == Rule::set_instruction => format!(
+            "set {};",
+            set_instruction.into_inner().nth(1).unwrap().as_str()
+        ),
+        name => {
+            let value = inner
+                .by_ref()
+                .find(|pair| {
+                    if pair.as_rule() == Rule::COMMENT {
+                        comments.push(pair.as_str().trim().to_owned());
+                        false
+                    } else {
+                        true
+                    }
+                })
+                .unwrap();
+
+            format!("{}: {};", name.as_str(), value.as_str())
+        }
+    };
+
+    // e.g. `width: 2; // wider at low zoom levels`
+    comments.extend(inner.map(|comment| comment.as_str().trim().to_owned()));
+
+    std::iter::once(declaration)
+        .chain(comments)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_rule(stylesheet: &mut String, indentation: &str, selectors: &[String], body: &[String]) {
+    let selector_separator = format!(",\n{}", indentation);
+    let selectors = selectors.join(&selector_separator);
+    writeln!(
+        stylesheet,
+        "{}{}{}{{",
+        indentation,
+        selectors,
+        brace_separator(&selectors, indentation)
+    )
+    .unwrap();
+
+    for line in body {
+        writeln!(stylesheet, "{}{}{}", indentation, INDENTATION, line).unwrap();
+    }
+
+    writeln!(stylesheet, "{}}}", indentation).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_mapcss, print_rules};
+    use crate::mapcss::parser::MapCssParser;
+
+    const STYLESHEET: &str = r#"
+        meta { title: "Round \"trip\""; version: "1.0"; }
+        /* roads */
+        way[highway=primary], way[highway="primary link"]|z12-14 {
+            color: #ff000080; width: 2.5px; dashes: 3,4;
+            text: eval(concat(tag("name"), " ", tag("ref"))); z-index: -1;
+            set .major; set "is road"=yes;
+        }
+        way::casing[!bridge?][tunnel?!]:not(*.minor):closed { width: 1m; linecap: round; }
+        relation[type=route] > [role=forward] way[name=~/^A\/B/i][oneway!~/no/] { text-position: line; }
+        relation[type=multipolygon] > [index=-1] relation > way|z-10[ele>=1500.5][maxspeed<50] {
+            opacity: 0.5; allow_overlap: true;
+        }
+        node:righthandtraffic[!noexit][amenity!=bench]!.hidden { text-anchor-vertical: below; }
+        @media (min-zoom: 12) and (max-zoom: 16) {
+            // inside a block
+            area:closed { fill-color: red; text-wrap-character: ";"; }
+            @supports not (user-agent: josm) { *[building] { width: 0; } }
+        }
+    "#;
+
+    #[test]
+    fn test_print_rules_round_trip() {
+        let (acknowledgement, rules, warnings) = MapCssParser::parse_mapcss(STYLESHEET).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        let printed = print_rules(acknowledgement.as_ref(), &rules);
+        let (reparsed_acknowledgement, reparsed_rules, warnings) =
+            MapCssParser::parse_mapcss(&printed).unwrap();
+
+        assert!(warnings.is_empty(), "{}", printed);
+        assert_eq!(reparsed_rules, rules, "{}", printed);
+        assert_eq!(
+            reparsed_acknowledgement.map(|acknowledgement| acknowledgement.title),
+            Some("Round \"trip\"".to_owned())
+        );
+        assert_eq!(
+            print_rules(None, &reparsed_rules),
+            print_rules(None, &rules)
+        );
+    }
+
+    #[test]
+    fn test_print_eval_strings_round_trip() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "way { text: eval(concat(tag(\"name\"), \"a\u{1}b \\\"c\\\" \\\\ d\\n\")); }",
+        )
+        .unwrap();
+
+        let printed = print_rules(None, &rules);
+        let (_, reparsed_rules, warnings) = MapCssParser::parse_mapcss(&printed).unwrap();
+
+        assert!(warnings.is_empty(), "{}", printed);
+        assert_eq!(reparsed_rules, rules, "{}", printed);
+    }
+
+    #[test]
+    fn test_format_mapcss() {
+        let formatted = format_mapcss(STYLESHEET).unwrap();
+
+        assert_eq!(
+            MapCssParser::parse_mapcss(&formatted).unwrap().1,
+            MapCssParser::parse_mapcss(STYLESHEET).unwrap().1
+        );
+        assert_eq!(format_mapcss(&formatted).unwrap(), formatted);
+
+        assert_eq!(
+            format_mapcss("/* roads */\nway[highway=primary],way[ \"highway\" = 'trunk' ]{color:red;width:2}\nnode{}").unwrap(),
+            "/* roads */\n\
+             way[highway=primary],\n\
+             way[highway=trunk] {\n    \
+                 color: red;\n    \
+                 width: 2;\n\
+             }\n\
+             \n\
+             node {\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_format_keeps_comments_within_selectors() {
+        let stylesheet = r#"
+            way /* 1 */ [highway] /* 2 */ , /* 3 */ node[amenity] { width: 1; }
+            way[highway] // 4
+            > node { width: 2; }
+            @media /* 5 */ (min-zoom: 12) // 6
+            {
+                area[ /* 7 */ building] { fill-color: red; }
+            }
+            line { width: eval(1 /* 8 */ + 2); }
+        "#;
+
+        let formatted = format_mapcss(stylesheet).unwrap();
+
+        for comment in 1..=8 {
+            assert!(
+                formatted.contains(&format!(" {} ", comment))
+                    || formatted.contains(&format!("// {}\n", comment)),
+                "comment {} is missing in {}",
+                comment,
+                formatted
+            );
+        }
+
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(&formatted).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(rules, MapCssParser::parse_mapcss(stylesheet).unwrap().1);
+        assert_eq!(format_mapcss(&formatted).unwrap(), formatted);
+    }
+}
@@ -1,9 +1,21 @@
+use crate::mapcss::parser::quote_tag_value;
+use regex::{Regex, RegexBuilder};
 use std::cmp::Eq;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+mod simplify;
+mod tag_number;
+mod traffic;
+
+pub use tag_number::TagNumber;
+pub use traffic::is_right_hand_traffic;
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Default)]
 pub enum SelectorCondition {
     /// Always evaluates to `true`, automatically set in case there are no conditions
+    #[default]
     True,
     /// Always evalutes to `false`
     False,
@@ -13,33 +25,82 @@ pub enum SelectorCondition {
     RangeZoomLevel(u8, u8),
     MaxZoomLevel(u8),
     Not(Rc<Selector>),
-    HasDescendant(Rc<Selector>),
+    /// `parent >[link conditions] child`, stored on the child. The link conditions test the
+    /// `role` of a relation member and its 1-based `index` in the parent (negative ones count
+    /// from the end)
+    HasParent(Rc<Selector>, Box<SelectorCondition>),
+    /// holds the name of the layer (subpart) like `casing` in `way::casing`
     GenericPseudoClass(String),
+    /// holds the name of a class assigned by `set .class;`
+    HasClass(String),
     HasTag(String),
+    /// `[!key]`
+    HasNotTag(String),
+    /// `[key?]`, the value is `yes`, `true` or `1`
+    TagIsTruthy(String),
+    /// `[key?!]`, the value is `no`, `false` or `0`
+    TagIsFalsy(String),
     HasExactTagValue(String, String),
     HasNotTagValue(String, String),
-    ValueGreaterThan(String, isize),
-    ValueGreaterThanEqual(String, isize),
-    ValueLessThan(String, isize),
-    ValueLessThanEqual(String, isize),
+    /// `[key=~/pattern/]`, does not match if the tag is not set
+    TagValueMatches(String, TagRegex),
+    /// `[key!~/pattern/]`, also matches if the tag is not set
+    TagValueNotMatches(String, TagRegex),
+    /// numeric comparisons, which do not match if the tag is not set or not a number
+    ValueGreaterThan(String, TagNumber),
+    ValueGreaterThanEqual(String, TagNumber),
+    ValueLessThan(String, TagNumber),
+    ValueLessThanEqual(String, TagNumber),
     ClosedPath,
+    PseudoClass(PseudoClass),
 
     List(Vec<SelectorCondition>),
 }
 
-impl Default for SelectorCondition {
-    fn default() -> Self {
-        SelectorCondition::True
+impl SelectorCondition {
+    /// Returns the layer the rule with these conditions applies to.
+    pub fn layer(&self) -> &str {
+        use SelectorCondition::*;
+
+        match self {
+            GenericPseudoClass(layer) => layer,
+            List(conditions) => conditions
+                .iter()
+                .find_map(|condition| match condition {
+                    GenericPseudoClass(layer) => Some(layer.as_str()),
+                    _ => None,
+                })
+                .unwrap_or(DEFAULT_LAYER),
+            _ => DEFAULT_LAYER,
+        }
     }
-}
 
-impl SelectorCondition {
-    /// Merges two (sets of) conditions together
-    pub fn add_condition(self, new: SelectorCondition) -> SelectorCondition {
+    /// Returns the key of the tag the element needs to have for the condition to match, e.g.
+    /// `highway` for `[highway=primary]`, but not for `[highway!=primary]`.
+    pub fn required_tag_key(&self) -> Option<&str> {
         use SelectorCondition::*;
 
-        // TODO: Check whether merging the two conditions actually makes sense and simplify
-        // e.g. merge(SelectorCondition::MaxZoomLevel(10), SelectorCondition::MinZoomLevel(11)) => SelectorCondition::False
+        match self {
+            HasTag(key)
+            | TagIsTruthy(key)
+            | TagIsFalsy(key)
+            | HasExactTagValue(key, _)
+            | TagValueMatches(key, _)
+            | ValueGreaterThan(key, _)
+            | ValueGreaterThanEqual(key, _)
+            | ValueLessThan(key, _)
+            | ValueLessThanEqual(key, _) => Some(key),
+            List(conditions) => conditions
+                .iter()
+                .find_map(|condition| condition.required_tag_key()),
+            _ => None,
+        }
+    }
+
+    /// Merges two (sets of) conditions together, see [`SelectorCondition::simplify`] for
+    /// resolving contradicting or redundant conditions.
+    pub fn add_condition(self, new: SelectorCondition) -> SelectorCondition {
+        use SelectorCondition::*;
 
         if new == True {
             return self;
@@ -58,11 +119,132 @@ impl SelectorCondition {
             }
             True => new,
 
-            _ => SelectorCondition::List(vec![self, new]),
+            _ => {
+                if let List(mut new_conditions) = new {
+                    new_conditions.insert(0, self);
+                    SelectorCondition::List(new_conditions)
+                } else {
+                    SelectorCondition::List(vec![self, new])
+                }
+            }
         }
     }
 }
 
+/// The pseudo classes of MapCSS 0.2 and JOSM besides `:closed`, which describe the geometry,
+/// topology or state of an element.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum PseudoClass {
+    /// a way that does not enclose an area
+    Unclosed,
+    /// an element with tags other than the ones for meta data like `source` or `note`
+    Tagged,
+    Untagged,
+    /// a closed way or a multipolygon
+    Area,
+    /// a node shared by multiple ways
+    Connection,
+    /// a node that is not part of any way
+    Unconnected,
+    /// an element located in a country driving on the right
+    RightHandTraffic,
+    /// an element that has not been uploaded yet, i.e. has a negative ID
+    New,
+}
+
+impl PseudoClass {
+    pub fn from_name(name: &str) -> Option<PseudoClass> {
+        use PseudoClass::*;
+
+        Some(match name {
+            "unclosed" => Unclosed,
+            "tagged" => Tagged,
+            "untagged" => Untagged,
+            "area" => Area,
+            "connection" => Connection,
+            "unconnected" => Unconnected,
+            "righthandtraffic" => RightHandTraffic,
+            "new" => New,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        use PseudoClass::*;
+
+        match self {
+            Unclosed => "unclosed",
+            Tagged => "tagged",
+            Untagged => "untagged",
+            Area => "area",
+            Connection => "connection",
+            Unconnected => "unconnected",
+            RightHandTraffic => "righthandtraffic",
+            New => "new",
+        }
+    }
+}
+
+/// The layer of rules without a subpart like `::casing`.
+pub const DEFAULT_LAYER: &str = "default";
+/// The subpart `::*`, whose rules apply to every layer.
+pub const ALL_LAYERS: &str = "*";
+
+/// A regular expression compiled when parsing the stylesheet, compared by its source.
+#[derive(Debug, Clone)]
+pub struct TagRegex {
+    /// the pattern and flags as written in the stylesheet
+    pattern: String,
+    flags: String,
+    regex: Regex,
+}
+
+impl TagRegex {
+    /// Compiles the pattern of a `/pattern/flags` literal, supporting the flags `i` (case
+    /// insensitive), `m` (multi line), `s` (`.` matches new lines) and `x` (verbose).
+    pub fn new(pattern: &str, flags: &str) -> Result<TagRegex, regex::Error> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(flags.contains('i'))
+            .multi_line(flags.contains('m'))
+            .dot_matches_new_line(flags.contains('s'))
+            .ignore_whitespace(flags.contains('x'))
+            .build()?;
+
+        Ok(TagRegex {
+            pattern: pattern.to_owned(),
+            flags: flags.to_owned(),
+            regex,
+        })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn flags(&self) -> &str {
+        &self.flags
+    }
+}
+
+impl PartialEq for TagRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.flags == other.flags
+    }
+}
+
+impl Eq for TagRegex {}
+
+impl Hash for TagRegex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pattern.hash(state);
+        self.flags.hash(state);
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct Selector {
     selector_type: SelectorType,
@@ -102,9 +284,133 @@ impl Selector {
     }
 }
 
+impl fmt::Display for SelectorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SelectorType::*;
+
+        write!(
+            f,
+            "{}",
+            match self {
+                Any => "*",
+                Meta => "meta",
+                Node => "node",
+                Way => "way",
+                Relation => "relation",
+                Area => "area",
+                Line => "line",
+                Canvas => "canvas",
+            }
+        )
+    }
+}
+
+/// Writes the selector as MapCSS, which parses to the same selector again, e.g.
+/// `relation[type=route] > [role=forward] way|z12-[highway]::casing`.
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the parent of a child selector is the first condition
+        let (parent, conditions) = match &self.conditions {
+            SelectorCondition::HasParent(..) => (Some(&self.conditions), &[][..]),
+            SelectorCondition::List(conditions) => match conditions.split_first() {
+                Some((parent @ SelectorCondition::HasParent(..), conditions)) => {
+                    (Some(parent), conditions)
+                }
+                _ => (None, &conditions[..]),
+            },
+            condition => (None, std::slice::from_ref(condition)),
+        };
+
+        if let Some(parent) = parent {
+            write!(f, "{} ", parent)?;
+        }
+
+        write!(f, "{}", self.selector_type)?;
+
+        for condition in conditions {
+            write!(f, "{}", condition)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the condition as it appears in a selector, e.g. `[highway=primary]` or `|z12-`. `False`,
+/// which only results from @media and @supports blocks, is written as `:not(*)`.
+impl fmt::Display for SelectorCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SelectorCondition::*;
+
+        match self {
+            True => Ok(()),
+            False => write!(f, ":not(*)"),
+            ExactZoomLevel(zoom_level) => write!(f, "|z{}", zoom_level),
+            MinZoomLevel(min_zoom_level) => write!(f, "|z{}-", min_zoom_level),
+            RangeZoomLevel(min_zoom_level, max_zoom_level) => {
+                write!(f, "|z{}-{}", min_zoom_level, max_zoom_level)
+            }
+            MaxZoomLevel(max_zoom_level) => write!(f, "|z-{}", max_zoom_level),
+            Not(selector) => match (selector.selector_type, &selector.conditions) {
+                (SelectorType::Any, TagIsTruthy(key)) => write!(f, "[!{}?]", quote_tag_value(key)),
+                (SelectorType::Any, HasClass(class)) => write!(f, "!.{}", class),
+                _ => write!(f, ":not({})", selector),
+            },
+            HasParent(parent, link_conditions) => write!(f, "{} >{}", parent, link_conditions),
+            GenericPseudoClass(layer) if layer == ALL_LAYERS => write!(f, "::*"),
+            GenericPseudoClass(layer) => write!(f, "::{}", quote_tag_value(layer)),
+            HasClass(class) => write!(f, ".{}", class),
+            HasTag(key) => write!(f, "[{}]", quote_tag_value(key)),
+            HasNotTag(key) => write!(f, "[!{}]", quote_tag_value(key)),
+            TagIsTruthy(key) => write!(f, "[{}?]", quote_tag_value(key)),
+            TagIsFalsy(key) => write!(f, "[{}?!]", quote_tag_value(key)),
+            HasExactTagValue(key, value) => {
+                write!(f, "[{}={}]", quote_tag_value(key), quote_tag_value(value))
+            }
+            HasNotTagValue(key, value) => {
+                write!(f, "[{}!={}]", quote_tag_value(key), quote_tag_value(value))
+            }
+            TagValueMatches(key, regex) => write!(f, "[{}=~{}]", quote_tag_value(key), regex),
+            TagValueNotMatches(key, regex) => write!(f, "[{}!~{}]", quote_tag_value(key), regex),
+            ValueGreaterThan(key, number) => write!(f, "[{}>{}]", quote_tag_value(key), number),
+            ValueGreaterThanEqual(key, number) => {
+                write!(f, "[{}>={}]", quote_tag_value(key), number)
+            }
+            ValueLessThan(key, number) => write!(f, "[{}<{}]", quote_tag_value(key), number),
+            ValueLessThanEqual(key, number) => {
+                write!(f, "[{}<={}]", quote_tag_value(key), number)
+            }
+            ClosedPath => write!(f, ":closed"),
+            PseudoClass(pseudo_class) => write!(f, ":{}", pseudo_class.name()),
+            List(conditions) => conditions
+                .iter()
+                .try_for_each(|condition| write!(f, "{}", condition)),
+        }
+    }
+}
+
+/// Writes the regular expression as `/pattern/flags` literal.
+impl fmt::Display for TagRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}/{}", self.pattern.replace('/', "\\/"), self.flags)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SelectorCondition;
+    use super::{SelectorCondition, TagRegex};
+
+    #[test]
+    fn test_tag_regex_flags() {
+        let regex = TagRegex::new("^b", "").unwrap();
+        assert!(regex.is_match("bus_stop"));
+        assert!(!regex.is_match("Bus_stop"));
+
+        let case_insensitive_regex = TagRegex::new("^b", "i").unwrap();
+        assert!(case_insensitive_regex.is_match("Bus_stop"));
+        assert_ne!(regex, case_insensitive_regex);
+
+        assert!(TagRegex::new("(unclosed", "").is_err());
+    }
 
     #[test]
     fn test_selector_condition_merge_true_simple() {
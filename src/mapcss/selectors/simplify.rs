@@ -0,0 +1,319 @@
+use super::{Selector, SelectorCondition, SelectorType};
+use crate::mapcss::declaration::{FALSY_VALUES, TRUTHY_VALUES};
+use std::rc::Rc;
+
+impl SelectorCondition {
+    /// Normalizes the conditions: nested lists are flattened, zoom levels are merged into a
+    /// single range, duplicate or implied tests are dropped and negations are resolved where
+    /// possible, e.g. `:not(*:not(*[bridge]))` to `[bridge]`. Returns `False` if the conditions
+    /// contradict each other like `[highway=primary][highway=secondary]` or `|z-10|z12-`.
+    pub fn simplify(self) -> SelectorCondition {
+        simplify_list(vec![self])
+    }
+}
+
+fn simplify_list(conditions: Vec<SelectorCondition>) -> SelectorCondition {
+    use SelectorCondition::*;
+
+    let mut simplified_conditions = Vec::with_capacity(conditions.len());
+
+    for condition in conditions {
+        let condition = match condition {
+            List(conditions) => simplify_list(conditions),
+            Not(selector) => simplify_not(&selector),
+            HasParent(parent, link_conditions) => {
+                let parent_conditions = parent.conditions().clone().simplify();
+
+                if parent_conditions == False {
+                    return False;
+                }
+
+                // link conditions are no tag tests, e.g. [index=1][index=-1] may both match
+                HasParent(
+                    Rc::new(Selector::new(parent.selector_type(), parent_conditions)),
+                    link_conditions,
+                )
+            }
+            condition => condition,
+        };
+
+        match condition {
+            True => (),
+            False => return False,
+            List(conditions) => simplified_conditions.extend(conditions),
+            condition => simplified_conditions.push(condition),
+        }
+    }
+
+    if !merge_zoom_levels(&mut simplified_conditions) {
+        return False;
+    }
+
+    let mut unique_conditions: Vec<SelectorCondition> = Vec::new();
+    for condition in simplified_conditions {
+        if !unique_conditions.contains(&condition) {
+            unique_conditions.push(condition);
+        }
+    }
+
+    for (index, condition) in unique_conditions.iter().enumerate() {
+        if unique_conditions[index + 1..]
+            .iter()
+            .any(|other| contradicts(condition, other) || contradicts(other, condition))
+        {
+            return False;
+        }
+    }
+
+    // drop the tests already covered by another one, like [highway] by [highway=primary]
+    let mut index = 0;
+    while index < unique_conditions.len() {
+        let is_implied = unique_conditions
+            .iter()
+            .enumerate()
+            .any(|(other_index, other)| {
+                other_index != index && implies(other, &unique_conditions[index])
+            });
+
+        if is_implied {
+            unique_conditions.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    match unique_conditions.len() {
+        0 => True,
+        1 => unique_conditions.pop().unwrap(),
+        _ => List(unique_conditions),
+    }
+}
+
+/// Resolves the negation of the selector into a single condition where possible.
+fn simplify_not(selector: &Selector) -> SelectorCondition {
+    use SelectorCondition::*;
+
+    let conditions = selector.conditions().clone().simplify();
+
+    if selector.selector_type() != SelectorType::Any {
+        return match conditions {
+            False => True,
+            conditions => Not(Rc::new(Selector::new(selector.selector_type(), conditions))),
+        };
+    }
+
+    match conditions {
+        True => False,
+        False => True,
+        Not(selector) if selector.selector_type() == SelectorType::Any => {
+            selector.conditions().clone()
+        }
+        HasTag(key) => HasNotTag(key),
+        HasNotTag(key) => HasTag(key),
+        HasExactTagValue(key, value) => HasNotTagValue(key, value),
+        HasNotTagValue(key, value) => HasExactTagValue(key, value),
+        TagValueMatches(key, regex) => TagValueNotMatches(key, regex),
+        TagValueNotMatches(key, regex) => TagValueMatches(key, regex),
+        conditions => Not(Rc::new(Selector::new(SelectorType::Any, conditions))),
+    }
+}
+
+/// Replaces all zoom level conditions by a single one at the position of the first, returns
+/// `false` if no zoom level fulfills all of them.
+fn merge_zoom_levels(conditions: &mut Vec<SelectorCondition>) -> bool {
+    use SelectorCondition::*;
+
+    let mut zoom_range: Option<(usize, u8, u8)> = None;
+    let mut index = 0;
+
+    while index < conditions.len() {
+        let (min_zoom_level, max_zoom_level) = match conditions[index] {
+            ExactZoomLevel(zoom_level) => (zoom_level, zoom_level),
+            MinZoomLevel(min_zoom_level) => (min_zoom_level, u8::MAX),
+            MaxZoomLevel(max_zoom_level) => (0, max_zoom_level),
+            RangeZoomLevel(min_zoom_level, max_zoom_level) => (min_zoom_level, max_zoom_level),
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+
+        conditions.remove(index);
+
+        zoom_range = Some(match zoom_range {
+            Some((position, min, max)) => {
+                (position, min.max(min_zoom_level), max.min(max_zoom_level))
+            }
+            None => (index, min_zoom_level, max_zoom_level),
+        });
+    }
+
+    let Some((position, min_zoom_level, max_zoom_level)) = zoom_range else {
+        return true;
+    };
+
+    if min_zoom_level > max_zoom_level {
+        return false;
+    }
+
+    let zoom_condition = match (min_zoom_level, max_zoom_level) {
+        // matches every zoom level
+        (0, u8::MAX) => return true,
+        (min, max) if min == max => ExactZoomLevel(min),
+        (min, u8::MAX) => MinZoomLevel(min),
+        (0, max) => MaxZoomLevel(max),
+        (min, max) => RangeZoomLevel(min, max),
+    };
+
+    conditions.insert(position, zoom_condition);
+
+    true
+}
+
+/// Returns whether no element fulfills both conditions.
+fn contradicts(a: &SelectorCondition, b: &SelectorCondition) -> bool {
+    use SelectorCondition::*;
+
+    match (a, b) {
+        (HasExactTagValue(key, value), HasExactTagValue(other_key, other_value)) => {
+            key == other_key && value != other_value
+        }
+        (HasExactTagValue(key, value), HasNotTagValue(other_key, other_value)) => {
+            key == other_key && value == other_value
+        }
+        (HasExactTagValue(key, value), TagIsTruthy(other_key)) => {
+            key == other_key && !TRUTHY_VALUES.contains(&value.as_str())
+        }
+        (HasExactTagValue(key, value), TagIsFalsy(other_key)) => {
+            key == other_key && !FALSY_VALUES.contains(&value.as_str())
+        }
+        (HasExactTagValue(key, value), TagValueMatches(other_key, regex)) => {
+            key == other_key && !regex.is_match(value)
+        }
+        (HasExactTagValue(key, value), TagValueNotMatches(other_key, regex)) => {
+            key == other_key && regex.is_match(value)
+        }
+        (TagIsTruthy(key), TagIsFalsy(other_key)) => key == other_key,
+        (condition, HasNotTag(key)) => condition.required_tag_key() == Some(key),
+        (condition, Not(selector)) => {
+            selector.selector_type() == SelectorType::Any && selector.conditions() == condition
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether every element fulfilling the first condition also fulfills the second one.
+fn implies(a: &SelectorCondition, b: &SelectorCondition) -> bool {
+    use SelectorCondition::*;
+
+    match (a, b) {
+        (HasTag(_), _) => false,
+        (condition, HasTag(key)) => condition.required_tag_key() == Some(key),
+        (HasExactTagValue(key, value), HasNotTagValue(other_key, other_value)) => {
+            key == other_key && value != other_value
+        }
+        (HasExactTagValue(key, value), TagIsTruthy(other_key)) => {
+            key == other_key && TRUTHY_VALUES.contains(&value.as_str())
+        }
+        (HasExactTagValue(key, value), TagIsFalsy(other_key)) => {
+            key == other_key && FALSY_VALUES.contains(&value.as_str())
+        }
+        (HasExactTagValue(key, value), TagValueMatches(other_key, regex)) => {
+            key == other_key && regex.is_match(value)
+        }
+        (HasExactTagValue(key, value), TagValueNotMatches(other_key, regex)) => {
+            key == other_key && !regex.is_match(value)
+        }
+        (HasNotTag(key), HasNotTagValue(other_key, _) | TagValueNotMatches(other_key, _)) => {
+            key == other_key
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mapcss::parser::MapCssParser;
+    use crate::mapcss::selectors::{SelectorCondition, SelectorType};
+
+    /// Parses the selector of a rule and returns its simplified conditions.
+    fn simplify(selector: &str) -> SelectorCondition {
+        let (_, rules, _) =
+            MapCssParser::parse_mapcss(&format!("{} {{ width: 1; }}", selector)).unwrap();
+
+        rules
+            .get(&SelectorType::Way)
+            .and_then(|rules| rules.first())
+            .map(|rule| rule.conditions.clone())
+            .unwrap_or(SelectorCondition::False)
+    }
+
+    #[test]
+    fn test_simplify_conditions() {
+        use SelectorCondition::*;
+
+        assert_eq!(simplify("way|z12-|z-16"), RangeZoomLevel(12, 16));
+        assert_eq!(simplify("way|z10-14|z12-18"), RangeZoomLevel(12, 14));
+        assert_eq!(simplify("way|z0-"), True);
+        assert_eq!(simplify("way|z14-|z-14"), ExactZoomLevel(14));
+        assert_eq!(
+            simplify("way[highway][highway=primary][highway=primary]"),
+            HasExactTagValue("highway".into(), "primary".into())
+        );
+        assert_eq!(
+            simplify("way[bridge=yes][bridge?][bridge!=no][name=~/^A/][name=\"A 13\"]"),
+            List(vec![
+                HasExactTagValue("bridge".into(), "yes".into()),
+                HasExactTagValue("name".into(), "A 13".into()),
+            ])
+        );
+        assert_eq!(
+            simplify("way:not(*:not(*[bridge])):not(*[tunnel])"),
+            List(vec![HasTag("bridge".into()), HasNotTag("tunnel".into())])
+        );
+        assert_eq!(
+            simplify("way[!name][name!=Vaduz]"),
+            HasNotTag("name".into())
+        );
+    }
+
+    #[test]
+    fn test_contradictions_are_dropped() {
+        for selector in [
+            "way|z-10|z12-",
+            "way[highway=primary][highway=secondary]",
+            "way[highway][!highway]",
+            "way[oneway?][oneway?!]",
+            "way[oneway=-1][oneway?]",
+            "way[name=Vaduz][name!=Vaduz]",
+            "way[name=Vaduz][name=~/^Schaan/]",
+            "way.major!.major",
+            "way:not(*)",
+            "relation[type=route][type=network] > way",
+        ] {
+            let (_, rules, warnings) =
+                MapCssParser::parse_mapcss(&format!("{} {{ width: 1; }}", selector)).unwrap();
+
+            assert!(rules.is_empty(), "{}", selector);
+            assert_eq!(
+                warnings
+                    .iter()
+                    .map(|warning| warning.message.as_str())
+                    .collect::<Vec<_>>(),
+                [format!(
+                    "Dropped selector {} which can never match.",
+                    selector
+                )]
+            );
+        }
+
+        let (_, rules, warnings) = MapCssParser::parse_mapcss(
+            "@media (min-zoom: 14) { way|z-12, way[highway] { width: 1; } }",
+        )
+        .unwrap();
+
+        assert_eq!(rules[&SelectorType::Way].len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].column, 25);
+    }
+}
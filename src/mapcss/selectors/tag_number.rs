@@ -0,0 +1,83 @@
+use crate::mapcss::parser::FloatSize;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A number of a tag value or a comparison in a selector like `[ele>1500.5]`. Units are
+/// converted to the default unit of OpenStreetMap for the kind of value, i.e. km/h for speeds,
+/// meters for lengths and tonnes for weights.
+#[derive(Debug, Clone, Copy)]
+pub struct TagNumber(FloatSize);
+
+impl TagNumber {
+    /// Parses a number with an optional unit, e.g. `50`, `-3.5`, `50 mph` or `3.5 m`. Returns
+    /// `None` for anything else, including unknown units.
+    pub fn parse(value: &str) -> Option<TagNumber> {
+        let value = value.trim();
+        let unit_start = value
+            .find(|char: char| !(char.is_ascii_digit() || char == '.' || char == '-'))
+            .unwrap_or(value.len());
+
+        let number = value[..unit_start].parse::<FloatSize>().ok()?;
+
+        let factor = match value[unit_start..].trim() {
+            "" | "km/h" | "m" | "t" => 1.0,
+            "mph" => 1.609_344,
+            "knots" => 1.852,
+            "km" => 1000.0,
+            "cm" => 0.01,
+            "mm" => 0.001,
+            "mi" => 1609.344,
+            "ft" => 0.3048,
+            "kg" => 0.001,
+            "lbs" => 0.000_453_592_37,
+            _ => return None,
+        };
+
+        Some(TagNumber(number * factor)).filter(|number| number.0.is_finite())
+    }
+
+    pub fn value(&self) -> FloatSize {
+        self.0
+    }
+}
+
+/// Writes the number in the default unit, which parses to the same number again.
+impl fmt::Display for TagNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl PartialEq for TagNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for TagNumber {}
+
+impl Hash for TagNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TagNumber;
+
+    #[test]
+    fn test_parse_tag_number() {
+        let parse = |value| TagNumber::parse(value).map(|number| number.value());
+
+        assert_eq!(parse("1500.5"), Some(1500.5));
+        assert_eq!(parse("-12"), Some(-12.0));
+        assert_eq!(parse("3.5 m"), Some(3.5));
+        assert_eq!(parse("2km"), Some(2000.0));
+        assert!((parse("50 mph").unwrap() - 80.4672).abs() < 1e-9);
+
+        assert_eq!(parse("none"), None);
+        assert_eq!(parse("50 parsecs"), None);
+        assert_eq!(parse(""), None);
+    }
+}
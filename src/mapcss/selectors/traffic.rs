@@ -0,0 +1,184 @@
+/// Simplified outlines (latitude, longitude) of the regions driving on the left, everywhere else
+/// drives on the right. Land borders are followed within a few kilometres, while coasts are only
+/// enclosed generously, so locations right at a border may still end up on the wrong side.
+#[rustfmt::skip]
+const LEFT_HAND_TRAFFIC_REGIONS: &[&[(f64, f64)]] = &[
+    // Great Britain, Ireland and the Isle of Man
+    &[
+        (49.7, -6.6), (50.5, -1.0), (50.65, 0.3), (50.85, 1.05), (51.05, 1.45), (51.3, 1.6),
+        (52.0, 1.9), (52.6, 2.0), (53.5, 1.0), (55.5, -1.0), (57.7, -1.5), (58.7, -2.0),
+        (60.9, -0.3), (61.0, -1.5), (59.0, -8.0), (56.5, -8.2), (55.6, -8.6), (54.3, -10.4),
+        (53.3, -10.4), (52.0, -10.7), (51.3, -10.0), (51.2, -8.0),
+    ],
+    // Channel Islands
+    &[(49.15, -2.75), (49.75, -2.75), (49.75, -2.2), (49.3, -1.99), (49.15, -1.99)],
+    // Malta
+    &[(35.78, 14.15), (36.1, 14.15), (36.1, 14.6), (35.78, 14.6)],
+    // Cyprus
+    &[(34.5, 32.2), (35.75, 32.2), (35.75, 34.65), (34.5, 34.65)],
+    // Japan
+    &[
+        (30.2, 130.2), (32.5, 128.4), (34.0, 129.0), (34.75, 129.2), (34.75, 129.6), (34.2, 130.2),
+        (36.5, 133.0), (40.5, 139.0), (42.2, 139.2), (43.4, 140.2), (45.0, 140.8), (45.6, 140.9),
+        (45.65, 141.8), (45.6, 142.1), (44.5, 143.6), (44.45, 145.3), (43.55, 145.35),
+        (43.38, 145.86), (42.8, 145.0), (41.85, 143.3), (41.0, 141.7), (39.5, 142.3), (37.0, 141.2),
+        (35.6, 141.0), (33.0, 140.2), (32.9, 139.4), (33.3, 136.0), (32.5, 133.0), (31.3, 131.5),
+        (30.2, 131.2),
+    ],
+    // Ryukyu Islands
+    &[(23.9, 122.85), (24.8, 122.85), (30.1, 129.5), (30.1, 131.2), (23.9, 131.2)],
+    // Hong Kong
+    &[
+        (22.13, 113.82), (22.42, 113.86), (22.5, 113.96), (22.52, 114.07), (22.545, 114.13),
+        (22.56, 114.23), (22.5, 114.44), (22.4, 114.5), (22.13, 114.5),
+    ],
+    // Macau
+    &[(22.215, 113.53), (22.215, 113.56), (22.11, 113.6), (22.11, 113.555), (22.165, 113.545)],
+    // India, Pakistan, Nepal, Bhutan, Bangladesh, Sri Lanka and the Maldives
+    &[
+        // Pakistan and Iran, Afghanistan and China
+        (25.1, 61.6), (26.2, 62.3), (27.2, 63.3), (28.0, 62.8), (29.85, 60.87), (29.4, 62.4),
+        (29.5, 64.2), (29.9, 66.3), (30.9, 66.4), (31.6, 68.0), (31.8, 69.3), (32.3, 69.45),
+        (32.9, 69.5), (33.1, 70.0), (33.5, 70.1), (33.75, 69.85), (33.95, 69.95), (34.1, 71.1),
+        (35.0, 71.5), (35.6, 71.6), (36.5, 71.5), (36.9, 72.5), (37.05, 74.55), (36.85, 75.43),
+        (35.88, 76.51),
+        // India and China along the Line of Actual Control
+        (35.5, 77.82), (34.9, 78.3), (34.3, 78.8), (33.5, 79.4), (32.7, 79.45), (31.9, 78.75),
+        (31.3, 79.1), (30.9, 79.9), (30.23, 81.03),
+        // Nepal, Sikkim, Bhutan and Arunachal Pradesh
+        (30.45, 81.5), (30.0, 82.2), (29.4, 83.6), (29.2, 84.2), (28.6, 85.2), (28.3, 85.9),
+        (27.99, 86.93), (27.8, 88.1), (28.13, 88.6), (27.9, 88.9), (27.35, 88.85), (27.25, 89.0),
+        (27.85, 89.25), (28.25, 89.9), (28.3, 90.4), (27.95, 91.6), (27.8, 91.7), (28.0, 92.5),
+        (28.6, 94.0), (29.0, 94.6), (29.45, 96.0), (28.2, 97.35),
+        // India and Bangladesh against Myanmar
+        (27.3, 96.9), (27.1, 96.1), (26.6, 95.4), (25.8, 95.0), (24.5, 94.7), (24.25, 94.3),
+        (23.9, 94.15), (23.5, 93.4), (22.0, 93.1), (22.0, 92.6), (21.3, 92.6), (21.15, 92.25),
+        (20.85, 92.33), (20.7, 92.36),
+        // the coast
+        (20.5, 92.3), (19.5, 87.0), (15.5, 82.0), (13.0, 80.6), (8.0, 82.2), (5.8, 81.0),
+        (5.8, 79.5), (-0.8, 73.9), (-0.8, 72.5), (12.5, 71.5), (20.5, 70.0), (22.0, 68.5),
+        (23.5, 67.5), (24.6, 66.5),
+    ],
+    // Andaman and Nicobar Islands
+    &[(6.5, 92.1), (13.7, 92.1), (13.7, 94.0), (6.5, 94.0)],
+    // Thailand, Malaysia, Singapore, Brunei, Indonesia, East Timor, Papua New Guinea, Solomon Islands
+    &[
+        // Thailand against Myanmar, Laos and Cambodia
+        (9.95, 98.5), (10.8, 98.75), (11.7, 99.65), (12.6, 99.25), (13.2, 99.1), (14.1, 98.6),
+        (15.3, 98.4), (15.8, 98.6), (16.7, 98.55), (17.6, 97.8), (18.3, 97.5), (18.6, 97.4),
+        (19.4, 97.8), (19.8, 98.0), (20.1, 98.9), (20.43, 99.88), (20.35, 100.1), (20.26, 100.4),
+        (19.6, 100.9), (19.0, 101.2), (18.3, 101.1), (17.9, 101.6), (17.88, 102.74), (18.4, 103.3),
+        (18.36, 103.65), (17.4, 104.81), (16.54, 104.74), (15.3, 105.5), (14.35, 105.2),
+        (14.35, 105.0), (14.4, 103.5), (14.35, 102.8), (13.7, 102.5), (12.7, 102.6), (12.0, 102.8),
+        (11.65, 102.91),
+        // the Gulf of Thailand, the South China Sea and Borneo
+        (11.55, 102.85), (9.0, 101.8), (6.5, 104.0), (4.9, 108.0), (4.6, 109.2), (7.2, 116.4),
+        (7.35, 117.25), (6.4, 117.85), (5.95, 118.2), (4.55, 118.95), (4.2, 119.5),
+        // Sulawesi, New Guinea and the Solomon Islands against the Philippines and Palau
+        (2.2, 122.0), (4.0, 125.3), (4.7, 127.2), (2.7, 129.0), (1.0, 131.5), (-0.8, 140.0),
+        (-0.8, 147.5), (-1.0, 150.5), (-3.2, 155.0), (-5.0, 157.8), (-9.8, 167.3), (-11.5, 170.0),
+        (-12.5, 168.9), (-12.0, 166.0), (-12.5, 160.0), (-11.8, 153.0), (-10.9, 142.2),
+        // the Timor Sea and the Indian Ocean
+        (-11.5, 125.0), (-11.3, 121.0), (-9.3, 113.0), (-7.2, 105.0), (-5.8, 102.0), (-3.3, 99.9),
+        (-0.8, 97.9), (2.5, 95.4), (6.2, 94.8), (7.5, 97.6), (9.6, 97.75),
+    ],
+    // Australia
+    &[(-44.0, 112.0), (-9.0, 112.0), (-9.0, 154.0), (-44.0, 154.0)],
+    // New Zealand
+    &[(-47.5, 166.0), (-34.0, 166.0), (-34.0, 179.0), (-47.5, 179.0)],
+    // southern and eastern Africa
+    &[
+        // Namibia and Zambia against Angola
+        (-17.25, 11.75), (-17.4, 14.2), (-17.4, 18.45), (-17.9, 20.85), (-17.5, 23.35),
+        (-16.2, 22.0), (-13.0, 22.0), (-13.0, 24.0), (-10.9, 24.0),
+        // Zambia, Tanzania and Uganda against the DR Congo, Burundi and Rwanda
+        (-12.26, 27.8), (-12.48, 28.3), (-12.85, 28.6), (-13.3, 29.3), (-12.5, 29.5),
+        (-11.97, 28.78), (-11.0, 28.6), (-10.37, 28.68), (-9.45, 28.72), (-8.5, 28.9),
+        (-8.25, 30.5), (-7.0, 30.1), (-5.9, 29.4), (-4.7, 29.45), (-4.45, 29.7), (-4.0, 30.0),
+        (-3.4, 30.6), (-2.9, 30.8), (-2.4, 30.85), (-1.8, 30.85), (-1.05, 30.47), (-1.25, 30.3),
+        (-1.43, 30.0), (-1.34, 29.74), (-1.38, 29.6), (-0.6, 29.6), (0.4, 29.87), (1.1, 30.2),
+        (1.5, 30.6), (2.2, 31.15), (2.4, 30.96), (2.86, 30.87), (3.5, 30.9),
+        // Uganda and Kenya against South Sudan, Ethiopia and Somalia
+        (3.75, 31.7), (3.6, 32.05), (3.75, 33.2), (4.22, 34.0), (4.62, 34.38), (5.0, 35.0),
+        (4.62, 35.9), (4.4, 36.1), (3.5, 39.0), (3.96, 41.89), (2.8, 41.0), (0.0, 41.0),
+        (-1.67, 41.56),
+        // the coast
+        (-10.5, 41.0), (-15.0, 41.2), (-20.0, 36.5), (-26.0, 33.5), (-35.2, 20.0), (-34.0, 17.8),
+        (-26.5, 14.6), (-17.2, 11.5),
+    ],
+    // Guyana and Suriname
+    &[
+        (8.55, -59.99), (7.2, -60.6), (5.2, -60.73), (3.9, -59.58), (3.38, -59.82), (2.0, -59.7),
+        (1.2, -58.8), (1.9, -56.5), (2.0, -55.5), (2.4, -55.0), (2.3, -54.6), (3.6, -54.0),
+        (5.75, -54.0), (6.2, -54.0), (9.0, -59.7),
+    ],
+    // Jamaica
+    &[(17.7, -78.4), (18.6, -78.4), (18.6, -76.2), (17.7, -76.2)],
+    // Trinidad and Tobago
+    &[
+        (9.98, -61.95), (10.5, -61.75), (10.72, -61.8), (10.9, -61.6), (11.4, -60.4), (9.95, -60.9),
+    ],
+];
+
+/// Returns whether vehicles drive on the right-hand side of the road at the given location.
+pub fn is_right_hand_traffic(lat: f64, lon: f64) -> bool {
+    !LEFT_HAND_TRAFFIC_REGIONS
+        .iter()
+        .any(|outline| is_inside(outline, lat, lon))
+}
+
+/// Returns whether the location lies inside the outline, following the even-odd rule.
+fn is_inside(outline: &[(f64, f64)], lat: f64, lon: f64) -> bool {
+    let mut is_inside = false;
+
+    for (&(lat_a, lon_a), &(lat_b, lon_b)) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+        // count the edges crossed by a ray heading east of the location
+        if (lat_a > lat) != (lat_b > lat)
+            && lon < lon_a + (lat - lat_a) / (lat_b - lat_a) * (lon_b - lon_a)
+        {
+            is_inside = !is_inside;
+        }
+    }
+
+    is_inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_right_hand_traffic;
+
+    #[test]
+    fn test_is_right_hand_traffic() {
+        // Berlin, New York, Seoul and Calais
+        assert!(is_right_hand_traffic(52.52, 13.40));
+        assert!(is_right_hand_traffic(40.71, -74.01));
+        assert!(is_right_hand_traffic(37.57, 126.98));
+        assert!(is_right_hand_traffic(50.95, 1.86));
+
+        // London, Tokyo, Sydney and Johannesburg
+        assert!(!is_right_hand_traffic(51.51, -0.13));
+        assert!(!is_right_hand_traffic(35.68, 139.69));
+        assert!(!is_right_hand_traffic(-33.87, 151.21));
+        assert!(!is_right_hand_traffic(-26.20, 28.05));
+
+        // right next to left-hand traffic: Lubumbashi, Luena, Vientiane, Mandalay, Lhasa, Kabul
+        // and Kashgar
+        assert!(is_right_hand_traffic(-11.66, 27.48));
+        assert!(is_right_hand_traffic(-11.78, 19.92));
+        assert!(is_right_hand_traffic(17.97, 102.60));
+        assert!(is_right_hand_traffic(21.97, 96.08));
+        assert!(is_right_hand_traffic(29.65, 91.10));
+        assert!(is_right_hand_traffic(34.53, 69.17));
+        assert!(is_right_hand_traffic(39.47, 75.99));
+
+        // and their left-hand neighbours: Ndola, Mongu, Nong Khai, Chiang Rai, Kathmandu,
+        // Peshawar and Gilgit
+        assert!(!is_right_hand_traffic(-12.97, 28.64));
+        assert!(!is_right_hand_traffic(-15.25, 23.13));
+        assert!(!is_right_hand_traffic(17.88, 102.74));
+        assert!(!is_right_hand_traffic(19.91, 99.84));
+        assert!(!is_right_hand_traffic(27.72, 85.32));
+        assert!(!is_right_hand_traffic(34.00, 71.57));
+        assert!(!is_right_hand_traffic(35.92, 74.31));
+    }
+}
@@ -1,481 +1,1065 @@
-use crate::data::{ElementData, NodeData, RelationData, WayData};
+use crate::data::{DataSet, ElementData, NodeData, RelationData, WayData};
 use crate::element::canvas::CanvasElement;
+use crate::element::label::LabelPlacement;
+use crate::elevation::hillshade::HillshadeSettings;
+use crate::elevation::ElevationModel;
 use crate::mapcss::declaration::{
-    MapCssDeclarationList, MapCssDeclarationProperty, MapCssDeclarationValueType, ToColorValue,
-    ToIntegerValue,
+    MapCssDeclarationList, MapCssDeclarationProperty, ToColorValue, ToIntegerValue, ToLengthValue,
 };
 use crate::mapcss::parser::IntSize;
-use std::cmp;
-use std::collections::{BTreeSet, HashMap, HashSet};
-use std::convert::TryInto;
+use crate::viewport::{BoundingBox, OutputSize, Viewport};
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use std::collections::HashMap;
+use std::io::Write;
 use std::num::NonZeroI64;
-use std::path::PathBuf;
-use std::time::{Instant, SystemTime};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Instant;
+
+/// The height of the image bands used for streaming is a multiple of this.
+const IMAGE_PART_SIZE: u32 = 64;
+
+/// The default amount of pixel memory a single image band may take up when streaming (256 MiB).
+const DEFAULT_BAND_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+/// The font size of labels in pixels, unless set by `font-size`.
+const DEFAULT_FONT_SIZE: f64 = 8.0;
 
 pub trait Painter {
-    /// Paints the given data styled by the mapcss ast and returns the filename of the saved file.
+    /// Paints the given data styled by the mapcss ast and returns the rendered image.
     fn paint(
         &mut self,
-        image_resolution_factor: f64,
+        output_size: OutputSize,
         mapcss_ast: MapCssDeclarationList,
         nid_to_node_data: HashMap<NonZeroI64, NodeData>,
         wid_to_way_data: HashMap<NonZeroI64, WayData>,
         rid_to_relation_data: HashMap<NonZeroI64, RelationData>,
-    ) -> PathBuf;
+    ) -> image::RgbaImage;
 }
 
-#[derive(Default)]
-pub struct PngPainter {}
+pub struct PngPainter {
+    /// Upper bound of the pixel memory (in bytes) of a single band when streaming the image.
+    band_memory_budget: usize,
+    /// used for hillshading the background, if given
+    elevation_model: Option<ElevationModel>,
+    /// used for drawing the labels of nodes, which are left out without a font
+    font: Option<FontArc>,
+}
+
+impl Default for PngPainter {
+    fn default() -> Self {
+        PngPainter::with_band_memory_budget(DEFAULT_BAND_MEMORY_BUDGET)
+    }
+}
 
 impl Painter for PngPainter {
     fn paint(
         &mut self,
-        image_resolution_factor: f64,
-        mapcss_ast: MapCssDeclarationList,
+        output_size: OutputSize,
+        mut mapcss_ast: MapCssDeclarationList,
         nid_to_node_data: HashMap<NonZeroI64, NodeData>,
         wid_to_way_data: HashMap<NonZeroI64, WayData>,
         rid_to_relation_data: HashMap<NonZeroI64, RelationData>,
-    ) -> PathBuf {
-        const IMAGE_PART_SIZE: u32 = 64;
+    ) -> image::RgbaImage {
+        let scene = Scene::new(
+            output_size,
+            &mut mapcss_ast,
+            &nid_to_node_data,
+            &wid_to_way_data,
+            &rid_to_relation_data,
+            self.elevation_model.as_ref(),
+            self.font.as_ref(),
+        );
 
-        let canvas = CanvasElement {};
+        let render_start_instant = Instant::now();
+        let mut stats = RenderStats::default();
 
-        let mut processed_ways = 0;
+        let band = scene.render_band(0, scene.height, &mut stats);
 
-        let mut min_x = i32::MAX;
-        let mut max_x = i32::MIN;
-        let mut min_y = i32::MAX;
-        let mut max_y = i32::MIN;
+        stats.log(render_start_instant, scene.ways.len());
 
-        for way_refs in wid_to_way_data.values().map(|x| x.refs()) {
-            for ref_node_id in way_refs {
-                let ref_data = nid_to_node_data.get(ref_node_id).unwrap();
+        band.buffer
+    }
+}
 
-                let lat = (ref_data.lat * image_resolution_factor).ceil() as i32;
-                let lon = (ref_data.lon * image_resolution_factor).ceil() as i32;
+impl PngPainter {
+    /// Creates a painter that keeps at most roughly `band_memory_budget` bytes of pixel data in
+    /// memory when streaming an image with [`PngPainter::paint_to_writer`].
+    pub fn with_band_memory_budget(band_memory_budget: usize) -> PngPainter {
+        PngPainter {
+            band_memory_budget,
+            elevation_model: None,
+            font: None,
+        }
+    }
 
-                min_x = cmp::min(min_x, lat);
-                max_x = cmp::max(max_x, lat);
+    /// Sets the elevation data used to shade the relief below all ways. The shading is
+    /// configured by the `hillshade-*` properties of the `canvas {}` block.
+    pub fn set_elevation_model(&mut self, elevation_model: ElevationModel) {
+        self.elevation_model = Some(elevation_model);
+    }
 
-                min_y = cmp::min(min_y, lon);
-                max_y = cmp::max(max_y, lon);
-            }
-        }
+    /// Sets the font of the labels, which are drawn on top of all ways. Their text is set by the
+    /// `text` property of nodes and placed by the `text-anchor-*`, `text-offset-y` and
+    /// `text-wrap-*` properties.
+    pub fn set_font(&mut self, font: FontArc) {
+        self.font = Some(font);
+    }
 
-        let image_width =
-            crate::round_up_to((((max_x - min_x) as f64).abs()) as u32, IMAGE_PART_SIZE);
-        let image_height =
-            crate::round_up_to((((max_y - min_y) as f64).abs()) as u32, IMAGE_PART_SIZE);
+    /// Paints the given data like [`Painter::paint`], but renders the image in horizontal bands
+    /// that are encoded into `writer` as PNG one after another, so that the whole image never
+    /// needs to be held in memory.
+    pub fn paint_to_writer<W: Write>(
+        &mut self,
+        output_size: OutputSize,
+        mut mapcss_ast: MapCssDeclarationList,
+        nid_to_node_data: HashMap<NonZeroI64, NodeData>,
+        wid_to_way_data: HashMap<NonZeroI64, WayData>,
+        rid_to_relation_data: HashMap<NonZeroI64, RelationData>,
+        writer: W,
+    ) -> Result<(), png::EncodingError> {
+        let scene = Scene::new(
+            output_size,
+            &mut mapcss_ast,
+            &nid_to_node_data,
+            &wid_to_way_data,
+            &rid_to_relation_data,
+            self.elevation_model.as_ref(),
+            self.font.as_ref(),
+        );
 
-        let background_color: image::Rgb<u8> = canvas.background_color(&mapcss_ast).into();
+        let band_height = self.band_height(scene.width);
+
+        info!(
+            "Streaming {}x{} image in bands of {} rows…",
+            scene.width, scene.height, band_height
+        );
 
-        let mut image_buffer: image::RgbImage =
-            image::ImageBuffer::from_pixel(image_height, image_width, background_color);
+        let mut encoder = png::Encoder::new(writer, scene.width, scene.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut png_writer = encoder.write_header()?;
+        let mut stream_writer = png_writer.stream_writer()?;
 
         let render_start_instant = Instant::now();
-        let mut no_inside_count: u32 = 0;
-        let mut flood_fill_pixel_count: u32 = 0;
+        let mut stats = RenderStats::default();
+
+        for top in (0..scene.height).step_by(band_height as usize) {
+            let band = scene.render_band(top, band_height.min(scene.height - top), &mut stats);
 
-        struct WayWithZIndex<'a> {
-            z_index: IntSize,
-            way_data: &'a WayData,
+            stream_writer.write_all(band.buffer.as_raw())?;
         }
 
-        info!("Sorting by z-index…");
-        let mut z_index_ordered_ways = wid_to_way_data
+        stream_writer.finish()?;
+
+        stats.log(render_start_instant, scene.ways.len());
+
+        Ok(())
+    }
+
+    /// Returns the number of rows of a band, which is a multiple of [`IMAGE_PART_SIZE`] that
+    /// fits into the memory budget (but at least [`IMAGE_PART_SIZE`] rows).
+    fn band_height(&self, image_width: u32) -> u32 {
+        let row_size = image_width as usize * BYTES_PER_PIXEL;
+        let rows = self.band_memory_budget / row_size.max(1);
+        let parts = (rows / IMAGE_PART_SIZE as usize).max(1);
+
+        (parts * IMAGE_PART_SIZE as usize).min((u32::MAX - u32::MAX % IMAGE_PART_SIZE) as usize)
+            as u32
+    }
+}
+
+/// A way in one of its layers together with the style properties the painter needs for
+/// rasterizing it.
+struct StyledWay<'a> {
+    way_data: &'a WayData,
+    z_index: IntSize,
+    color: image::Rgba<u8>,
+    /// areas are only filled if a fill color is set
+    fill_color: Option<image::Rgba<u8>>,
+    width: u32,
+    /// the topmost image row the way may touch, may lie outside of the image
+    min_image_y: i64,
+    /// the bottommost image row the way may touch, may lie outside of the image
+    max_image_y: i64,
+}
+
+/// The label of a node in one of its layers, already broken into lines and placed.
+struct StyledLabel {
+    lines: Vec<String>,
+    z_index: IntSize,
+    color: image::Rgba<u8>,
+    scale: PxScale,
+    /// the distance between the tops of two lines in pixels
+    line_height: f64,
+    /// the top left corner of the first line in image coordinates
+    top_left: (f64, f64),
+}
+
+/// Everything that is needed to rasterize any band of the image, computed once up front.
+struct Scene<'a> {
+    viewport: Viewport,
+    hillshade: Option<(&'a ElevationModel, HillshadeSettings)>,
+    nid_to_node_data: &'a HashMap<NonZeroI64, NodeData>,
+    /// the visible layers of all ways, ordered by their z-index
+    ways: Vec<StyledWay<'a>>,
+    font: Option<&'a FontArc>,
+    /// the labels of all nodes, ordered by their z-index, only resolved if there is a font
+    labels: Vec<StyledLabel>,
+    background_color: image::Rgba<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Scene<'a> {
+    fn new(
+        output_size: OutputSize,
+        mapcss_ast: &mut MapCssDeclarationList,
+        nid_to_node_data: &'a HashMap<NonZeroI64, NodeData>,
+        wid_to_way_data: &'a HashMap<NonZeroI64, WayData>,
+        rid_to_relation_data: &HashMap<NonZeroI64, RelationData>,
+        elevation_model: Option<&'a ElevationModel>,
+        font: Option<&'a FontArc>,
+    ) -> Scene<'a> {
+        let canvas = CanvasElement {};
+        let hillshade_settings = canvas.hillshade_settings(mapcss_ast);
+
+        let extent = BoundingBox::from_nodes(
+            wid_to_way_data
+                .values()
+                .flat_map(|way_data| way_data.refs())
+                .filter_map(|nid| nid_to_node_data.get(nid)),
+        )
+        .unwrap_or(BoundingBox {
+            min_lat: 0.0,
+            max_lat: 0.0,
+            min_lon: 0.0,
+            max_lon: 0.0,
+        });
+
+        let viewport = Viewport::new(&extent, output_size);
+
+        info!(
+            "Rendering at zoom level {:.2} ({:?}).",
+            viewport.zoom_level(),
+            output_size.mode()
+        );
+        mapcss_ast.set_zoom_level(viewport.zoom_level());
+
+        let background_color: image::Rgba<u8> = canvas.background_color(mapcss_ast).into();
+
+        let data_set = DataSet::new(nid_to_node_data, wid_to_way_data, rid_to_relation_data);
+        let layers = mapcss_ast.layers();
+
+        info!(
+            "Resolving styles of {} layers and sorting by z-index…",
+            layers.len()
+        );
+        let mut ways = wid_to_way_data
             .values()
-            .map(|way_data| WayWithZIndex {
-                z_index: mapcss_ast
-                    .search_or_default(
+            .flat_map(|way_data| layers.iter().map(move |layer| (way_data, *layer)))
+            .filter_map(|(way_data, layer)| {
+                // ground lengths are resolved at the first node of the way, ways without any
+                // of their nodes in the extract are not drawn at all
+                let first_node_data = way_data
+                    .refs()
+                    .iter()
+                    .find_map(|nid| nid_to_node_data.get(nid))?;
+
+                let color: image::Rgba<u8> = mapcss_ast
+                    .search_cascading_in_layer(
                         Box::new(way_data.clone()),
+                        Some(&data_set),
+                        layer,
+                        &MapCssDeclarationProperty::Color,
+                    )?
+                    .to_color()
+                    .into();
+
+                let fill_color = mapcss_ast
+                    .search_cascading_in_layer(
+                        Box::new(way_data.clone()),
+                        Some(&data_set),
+                        layer,
+                        &MapCssDeclarationProperty::FillColor,
+                    )
+                    .map(|x| x.to_color().into());
+
+                let width = mapcss_ast
+                    .search_cascading_in_layer(
+                        Box::new(way_data.clone()),
+                        Some(&data_set),
+                        layer,
+                        &MapCssDeclarationProperty::Width,
+                    )
+                    .map(|x| {
+                        x.to_length()
+                            .to_pixels(viewport.meters_per_pixel(first_node_data.lat))
+                    })
+                    .unwrap_or(0.0);
+
+                if width <= 0.0 || width.is_nan() {
+                    return None;
+                }
+
+                // ways thinner than a pixel are still drawn with a single pixel
+                let width = (width.round() as u32).max(1);
+
+                let z_index = mapcss_ast
+                    .search_cascading_in_layer(
+                        Box::new(way_data.clone()),
+                        Some(&data_set),
+                        layer,
                         &MapCssDeclarationProperty::ZIndex,
-                        &MapCssDeclarationValueType::Integer(0),
                     )
-                    .to_integer(),
-                way_data,
+                    .map(|x| x.to_integer())
+                    .unwrap_or(0);
+
+                // the line drawing algorithm may round to the neighbouring pixel rows
+                let (min_image_y, max_image_y) = way_data
+                    .refs()
+                    .iter()
+                    .filter_map(|nid| nid_to_node_data.get(nid))
+                    .map(|node_data| viewport.project(node_data.lat, node_data.lon).1)
+                    .fold((f64::MAX, f64::MIN), |(min, max), image_y| {
+                        (min.min(image_y), max.max(image_y))
+                    });
+
+                Some(StyledWay {
+                    way_data,
+                    z_index,
+                    color,
+                    fill_color,
+                    width,
+                    min_image_y: min_image_y.floor() as i64 - 1,
+                    max_image_y: max_image_y.ceil() as i64 + 1,
+                })
             })
             .collect::<Vec<_>>();
 
-        dbg!(z_index_ordered_ways.len());
+        ways.sort_by_key(|way| way.z_index);
 
-        z_index_ordered_ways.sort_by(|a, b| a.z_index.cmp(&b.z_index));
+        debug!("{} ways to rasterize.", ways.len());
 
-        info!("Rasterizing ways…");
-        for way_data in z_index_ordered_ways.iter().map(|x| x.way_data) {
-            processed_ways += 1;
+        // labels are left out without a font to measure and draw them
+        let mut labels = nid_to_node_data
+            .values()
+            .filter(|_| font.is_some())
+            .flat_map(|node_data| layers.iter().map(move |layer| (node_data, *layer)))
+            .filter_map(|(node_data, layer)| {
+                let font = font?;
+                let search = |property| {
+                    mapcss_ast.search_cascading_in_layer(
+                        Box::new(node_data.clone()),
+                        Some(&data_set),
+                        layer,
+                        &property,
+                    )
+                };
 
-            if processed_ways % 15000 == 0 {
-                info!("{} ways rendered…", processed_ways);
-            }
+                let text = search(MapCssDeclarationProperty::Text)?.to_string();
 
-            let way_refs = way_data.refs();
+                if text.is_empty() {
+                    return None;
+                }
 
-            let way_color: Option<image::Rgba<u8>> = mapcss_ast
-                .search_cascading(
-                    Box::new(way_data.clone()),
-                    &MapCssDeclarationProperty::Color,
-                )
-                .map(|x| x.to_color().into());
+                let font_size = search(MapCssDeclarationProperty::FontSize)
+                    .map(|x| {
+                        x.to_length()
+                            .to_pixels(viewport.meters_per_pixel(node_data.lat))
+                    })
+                    .unwrap_or(DEFAULT_FONT_SIZE);
 
-            if way_color.is_none() {
-                continue;
-            }
+                if font_size <= 0.0 || font_size.is_nan() {
+                    return None;
+                }
 
-            let way_color = way_color.unwrap();
+                let color = search(MapCssDeclarationProperty::TextColor)
+                    .map(|x| x.to_color().into())
+                    .unwrap_or(image::Rgba([0, 0, 0, 255]));
+
+                let z_index = search(MapCssDeclarationProperty::ZIndex)
+                    .map(|x| x.to_integer())
+                    .unwrap_or(0);
+
+                let placement = LabelPlacement::new(node_data, mapcss_ast, Some(&data_set), layer);
+
+                let scale = PxScale::from(font_size as f32);
+                let scaled_font = font.as_scaled(scale);
+                // kerning is left out, like when drawing the label
+                let text_width = |text: &str| {
+                    text.chars()
+                        .map(|character| scaled_font.h_advance(scaled_font.glyph_id(character)))
+                        .sum::<f32>() as f64
+                };
+                let line_height = (scaled_font.height() + scaled_font.line_gap()) as f64;
+
+                let lines = placement.wrap(&text, text_width);
+                let label_size = (
+                    lines
+                        .iter()
+                        .map(|line| text_width(line))
+                        .fold(0.0, f64::max),
+                    lines.len() as f64 * line_height,
+                );
+
+                Some(StyledLabel {
+                    top_left: placement
+                        .top_left(viewport.project(node_data.lat, node_data.lon), label_size),
+                    lines,
+                    z_index,
+                    color,
+                    scale,
+                    line_height,
+                })
+            })
+            .collect::<Vec<_>>();
 
-            let way_fill_color = mapcss_ast
-                .search_cascading(
-                    Box::new(way_data.clone()),
-                    &MapCssDeclarationProperty::FillColor,
-                )
-                .map(|x| x.to_color().into())
-                .unwrap_or(way_color);
+        labels.sort_by_key(|label| label.z_index);
+
+        debug!("{} labels to draw.", labels.len());
+
+        Scene {
+            width: viewport.width(),
+            height: viewport.height(),
+            viewport,
+            hillshade: elevation_model
+                .filter(|_| hillshade_settings.opacity > 0.0)
+                .map(|elevation_model| (elevation_model, hillshade_settings)),
+            nid_to_node_data,
+            ways,
+            font,
+            labels,
+            background_color,
+        }
+    }
 
-            let way_width: u32 = mapcss_ast
-                .search_cascading(
-                    Box::new(way_data.clone()),
-                    &MapCssDeclarationProperty::Width,
-                )
-                .map(|x| x.to_integer())
-                .and_then(|x| x.try_into().ok())
-                .unwrap_or(0);
+    /// Rasterizes all ways touching the rows `[top, top + height)` of the image.
+    fn render_band(&self, top: u32, height: u32, stats: &mut RenderStats) -> ImageBand {
+        let mut band = ImageBand {
+            top,
+            buffer: image::ImageBuffer::from_pixel(self.width, height, self.background_color),
+        };
+
+        if let Some((elevation_model, hillshade_settings)) = self.hillshade {
+            self.shade_relief(&mut band, elevation_model, &hillshade_settings);
+        }
 
-            if way_width == 0 {
+        for styled_way in &self.ways {
+            if styled_way.max_image_y < top as i64
+                || styled_way.min_image_y >= (top + height) as i64
+            {
                 continue;
             }
 
-            let is_closed_way = way_data.has_closed_path();
-
-            let mut pixeled_min_x_coordinates: (u32, u32) = (u32::MAX, u32::MAX);
-            let mut pixeled_max_x_coordinates: (u32, u32) = (u32::MIN, u32::MIN);
-
-            // draw the outline of the way and remember it
-            let mut outline_pixels: HashMap<u32, BTreeSet<u32>> = HashMap::new();
-
-            assert!(!way_refs.is_empty());
-
-            for ref_node_ids in way_refs[..].windows(2) {
-                let [node_a_id, node_b_id] = ref_node_ids else { unreachable!() };
-
-                let node_a = nid_to_node_data.get(node_a_id).unwrap();
-                let node_b = nid_to_node_data.get(node_b_id).unwrap();
-
-                for ((x, y), _alpha) in line_drawing::XiaolinWu::<f64, i32>::new(
-                    (
-                        node_a.lat * image_resolution_factor,
-                        node_a.lon * image_resolution_factor,
-                    ),
-                    (
-                        node_b.lat * image_resolution_factor,
-                        node_b.lon * image_resolution_factor,
-                    ),
-                ) {
-                    // rotate image by 270 degrees means we need to swap x and y and the y pixel needs to be subtracted from the image width.
-                    // Subtract / Add 2 from it as the image buffer uses 0-indexing AND we may not write to the last pixel.
-                    let image_x = (y - min_y + 2) as u32;
-                    let image_y = image_width - 2 - (x - min_x) as u32;
-
-                    if way_width == 1 {
-                        image_buffer.put_pixel(
-                            image_x,
-                            image_y,
-                            image::Rgb([way_color[0], way_color[1], way_color[2]]),
-                        );
-
-                        if is_closed_way {
-                            outline_pixels.entry(image_y).or_default().insert(image_x);
-
-                            // speedup "are we inside the drawn way boundaries?"
-                            if image_x > pixeled_max_x_coordinates.0 {
-                                pixeled_max_x_coordinates = (image_x, image_y);
-                            }
-
-                            if image_x < pixeled_min_x_coordinates.0 {
-                                pixeled_min_x_coordinates = (image_x, image_y);
-                            }
-                        }
-                    } else {
-                        let start_x = if way_width / 2 > image_x {
-                            0
-                        } else {
-                            image_x - way_width / 2
-                        };
-                        let end_x = (image_x + way_width / 2).min(image_width - 1);
-
-                        for x in start_x..=end_x {
-                            image_buffer.put_pixel(
-                                x,
-                                image_y,
-                                image::Rgb([
-                                    way_fill_color[0],
-                                    way_fill_color[1],
-                                    way_fill_color[2],
-                                ]),
-                            );
-
-                            if is_closed_way {
-                                outline_pixels.entry(image_y).or_default().insert(x);
-
-                                // speedup "are we inside the drawn way boundaries?"
-                                if image_x > pixeled_max_x_coordinates.0 {
-                                    pixeled_max_x_coordinates = (image_x, image_y);
-                                }
-
-                                if image_x < pixeled_min_x_coordinates.0 {
-                                    pixeled_min_x_coordinates = (image_x, image_y);
-                                }
-                            }
-                        }
-                    }
+            // count each way only in the first band it touches
+            if styled_way.min_image_y.max(0) >= top as i64 {
+                stats.processed_ways += 1;
+
+                if stats.processed_ways.is_multiple_of(15000) {
+                    info!("{} ways rendered…", stats.processed_ways);
                 }
             }
 
-            // determine whether we can fill the area
-            if is_closed_way
-                && pixeled_min_x_coordinates != pixeled_max_x_coordinates
-                && !are_image_coordinates_horizontally_next_to_each_other(
-                    pixeled_min_x_coordinates,
-                    pixeled_max_x_coordinates,
-                )
-            {
-                let min_y = pixeled_min_x_coordinates.1.min(pixeled_max_x_coordinates.1);
-                let max_y = pixeled_min_x_coordinates.1.max(pixeled_max_x_coordinates.1);
+            self.rasterize_way(styled_way, &mut band, stats);
+        }
 
-                if let Some((inner_x, inner_y)) = get_inside_point(min_y, max_y, &outline_pixels) {
-                    if inner_x >= image_buffer.width() || inner_y >= image_buffer.height() {
-                        panic!("inner point is bigger than the image's dimension(s)!");
-                    }
+        if let Some(font) = self.font {
+            for label in &self.labels {
+                let label_bottom = label.top_left.1 + label.lines.len() as f64 * label.line_height;
 
-                    for flood_filled_pixel in
-                        get_flood_filled_pixels((inner_x, inner_y), &mut outline_pixels)
-                    {
-                        // validate results
-                        if flood_filled_pixel.0 >= image_buffer.width()
-                            || flood_filled_pixel.1 >= image_buffer.height()
-                        {
-                            warn!("Ignoring flood filled pixel that is out of the image canvas.");
-                            continue;
-                        }
-
-                        flood_fill_pixel_count += 1;
-
-                        image_buffer.put_pixel(
-                            flood_filled_pixel.0,
-                            flood_filled_pixel.1,
-                            image::Rgb([way_fill_color[0], way_fill_color[1], way_fill_color[2]]),
-                        );
-                    }
-                } else {
-                    no_inside_count += 1;
-                    // dbg!(pixeled_min_x_coordinates, pixeled_max_x_coordinates);
+                if label_bottom < top as f64 || label.top_left.1 >= (top + height) as f64 {
+                    continue;
                 }
+
+                self.draw_label(font, label, &mut band);
             }
         }
 
-        let render_duration = render_start_instant.elapsed();
+        band
+    }
 
-        info!(
-            "Rendering took {:.2} s. {:.2} ways/sec. Saving …",
-            render_duration.as_secs_f32(),
-            processed_ways as f64 / (render_duration.as_nanos() as f64 * 1e-9)
-        );
+    /// Draws the lines of the label glyph by glyph, blending their edges with the band.
+    fn draw_label(&self, font: &FontArc, label: &StyledLabel, band: &mut ImageBand) {
+        let scaled_font = font.as_scaled(label.scale);
+
+        for (index, line) in label.lines.iter().enumerate() {
+            let baseline =
+                label.top_left.1 + index as f64 * label.line_height + scaled_font.ascent() as f64;
+            let mut x = label.top_left.0 as f32;
+
+            for character in line.chars() {
+                let glyph_id = scaled_font.glyph_id(character);
+                let glyph = glyph_id
+                    .with_scale_and_position(label.scale, ab_glyph::point(x, baseline as f32));
+                x += scaled_font.h_advance(glyph_id);
+
+                // e.g. spaces have no outline
+                let Some(outlined_glyph) = font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined_glyph.px_bounds();
+
+                outlined_glyph.draw(|glyph_x, glyph_y, coverage| {
+                    let image_x = bounds.min.x as i64 + glyph_x as i64;
+                    let image_y = bounds.min.y as i64 + glyph_y as i64;
+
+                    if (0..self.width as i64).contains(&image_x) && image_y >= 0 {
+                        band.blend_pixel(image_x as u32, image_y as u32, label.color, coverage);
+                    }
+                });
+            }
+        }
+    }
 
-        dbg!(image_height);
-        dbg!(image_width);
-        dbg!(flood_fill_pixel_count);
+    /// Shades the background of the band by the relief, before anything else is drawn on it.
+    fn shade_relief(
+        &self,
+        band: &mut ImageBand,
+        elevation_model: &ElevationModel,
+        hillshade_settings: &HillshadeSettings,
+    ) {
+        for (x, y, pixel) in band.buffer.enumerate_pixels_mut() {
+            let (lat, lon) = self
+                .viewport
+                .unproject(x as f64 + 0.5, (band.top + y) as f64 + 0.5);
+
+            if let Some(illumination) = hillshade_settings.illumination(
+                elevation_model,
+                lat,
+                lon,
+                self.viewport.meters_per_pixel(lat),
+            ) {
+                *pixel = hillshade_settings.shade(*pixel, illumination);
+            }
+        }
+    }
 
-        let file_path = PathBuf::from(format!(
-            "render-{}.png",
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        ));
+    fn rasterize_way(&self, styled_way: &StyledWay, band: &mut ImageBand, stats: &mut RenderStats) {
+        let way_color = styled_way.color;
+        let way_fill_color = styled_way.fill_color;
+        let way_width = styled_way.width;
+        let half_width = (way_width / 2) as i32;
+
+        let points: Vec<(f64, f64)> = styled_way
+            .way_data
+            .refs()
+            .iter()
+            // nodes missing from the extract are skipped
+            .filter_map(|nid| self.nid_to_node_data.get(nid))
+            .map(|node_data| self.viewport.project(node_data.lat, node_data.lon))
+            .collect();
+
+        assert!(!points.is_empty());
+
+        // closed ways are only filled if the style sets a fill color, e.g. not for contour lines
+        let area_fill_color = way_fill_color.filter(|_| styled_way.way_data.has_closed_path());
+
+        if let Some(area_fill_color) = area_fill_color {
+            // only the rows of the band the area covers
+            let top = styled_way.min_image_y.max(band.top as i64) as u32;
+            let bottom = styled_way
+                .max_image_y
+                .min(band.top as i64 + band.buffer.height() as i64 - 1);
+
+            if bottom >= top as i64 {
+                stats.filled_pixel_count +=
+                    band.fill_polygon(&points, top..=bottom as u32, area_fill_color);
+            }
+        }
 
-        let save_start_instant = Instant::now();
+        // thin ways are drawn in their color, wider ones in their fill color if there is one
+        let pixel_color = match way_fill_color {
+            Some(way_fill_color) if way_width > 1 => way_fill_color,
+            _ => way_color,
+        };
+
+        // the outline is drawn on top of the filled area
+        for segment in points.windows(2) {
+            let [point_a, point_b] = segment else {
+                unreachable!()
+            };
+
+            // the line drawing algorithm may round to the neighbouring pixel rows
+            if point_a.1.max(point_b.1) + 1.0 < band.top as f64
+                || point_a.1.min(point_b.1) - 1.0 >= (band.top + band.buffer.height()) as f64
+            {
+                continue;
+            }
 
-        image_buffer.save(&file_path).unwrap();
+            for ((x, y), _alpha) in line_drawing::XiaolinWu::<f64, i32>::new(*point_a, *point_b) {
+                if y < 0 {
+                    continue;
+                }
 
-        info!(
-            "Image saved successfully, took {:.2} s.",
-            save_start_instant.elapsed().as_secs_f32()
-        );
-        if no_inside_count > 0 {
-            warn!("couldn't find a starting point to flood fill {no_inside_count} time(s)");
+                for image_x in (x - half_width)..=(x + half_width) {
+                    if (0..self.width as i32).contains(&image_x) {
+                        band.put_pixel(image_x as u32, y as u32, pixel_color);
+                    }
+                }
+            }
         }
-
-        file_path
     }
 }
 
-/// Determines whether the given point is inside the outline given by `outline_pixels`,
-/// taking into consideration that there can be multiple outlines at the same level.
-fn is_inside(image_point: &(u32, u32), outline_pixels: &HashMap<u32, BTreeSet<u32>>) -> bool {
-    if let Some(outline_x_pixels) = outline_pixels.get(&image_point.1) {
-        // TODO: Clarify what happens if we have something like this:
-        // "x |", where x is the left-most pixel possible and hence the x is completely
-        // bordered by the outline
-        if outline_x_pixels.len() < 2 {
-            return false;
-        }
-
-        // Expected results:
-        // |   x  | => is inside
-        // |  x => is not inside (? see TODO above) - tbd
-        // x | => is not inside (? see TODO above) - tbd
-        // |  |  x => is not inside
-        // |  |   |  x  | => is inside
-        // |  | x |     | => is not inside
-
-        (outline_x_pixels.first().unwrap()..outline_x_pixels.last().unwrap())
-            .contains(&&image_point.0)
-            && !outline_x_pixels.contains(&image_point.0)
-    } else {
-        false
-    }
+/// A horizontal band of the image, holding the image rows `[top, top + buffer.height())`.
+struct ImageBand {
+    top: u32,
+    buffer: image::RgbaImage,
 }
 
-fn are_image_coordinates_horizontally_next_to_each_other(a: (u32, u32), b: (u32, u32)) -> bool {
-    if a.1 != b.1 {
-        // not horizontally on the same line
-        return false;
+impl ImageBand {
+    /// Puts the pixel at the given image coordinates, ignoring it in case it lies outside of
+    /// this band.
+    #[inline]
+    fn put_pixel(&mut self, image_x: u32, image_y: u32, color: image::Rgba<u8>) {
+        if image_y < self.top || image_y - self.top >= self.buffer.height() {
+            return;
+        }
+
+        self.buffer.put_pixel(image_x, image_y - self.top, color);
     }
 
-    // check if they are next to each other
-    a.0.abs_diff(b.0) == 1
-}
+    /// Blends the color over the pixel at the given image coordinates by the coverage between 0
+    /// and 1, ignoring it in case it lies outside of this band.
+    fn blend_pixel(&mut self, image_x: u32, image_y: u32, color: image::Rgba<u8>, coverage: f32) {
+        if image_y < self.top || image_y - self.top >= self.buffer.height() {
+            return;
+        }
 
-fn get_inside_point(
-    min_y: u32,
-    max_y: u32,
-    outline_pixels: &HashMap<u32, BTreeSet<u32>>,
-) -> Option<(u32, u32)> {
-    debug_assert!(min_y < max_y);
-
-    for y in min_y..=max_y {
-        // find hole in the outlined pixels at the height of the y
-        if let Some(x_pixels) = outline_pixels.get(&y) {
-            if x_pixels.len() < 2 {
-                continue;
-            }
+        let alpha = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+        let pixel = self.buffer.get_pixel_mut(image_x, image_y - self.top);
 
-            let mut last_x = x_pixels.first().unwrap();
+        for channel in 0..3 {
+            pixel[channel] = (pixel[channel] as f32 * (1.0 - alpha) + color[channel] as f32 * alpha)
+                .round() as u8;
+        }
+    }
 
-            // Invariant: The iterator is sorted ascending.
-            for x_pixel in x_pixels.iter().skip(1) {
-                if x_pixel - last_x != 1 {
-                    return Some((last_x + 1, y));
+    /// Fills the pixels of the given image rows whose centers lie inside the polygon, which is
+    /// closed implicitly. Overlapping parts, like the hole of a ring drawn as one outline, are
+    /// left out (even-odd rule). Returns the number of filled pixels.
+    fn fill_polygon(
+        &mut self,
+        points: &[(f64, f64)],
+        image_rows: RangeInclusive<u32>,
+        color: image::Rgba<u8>,
+    ) -> u32 {
+        let image_width = self.buffer.width();
+        let mut filled_pixel_count = 0;
+        let mut crossings = Vec::new();
+
+        for image_y in image_rows {
+            let scanline_y = image_y as f64 + 0.5;
+
+            crossings.clear();
+            crossings.extend(
+                points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .filter(|(point_a, point_b)| {
+                        (point_a.1 <= scanline_y) != (point_b.1 <= scanline_y)
+                    })
+                    .map(|(point_a, point_b)| {
+                        point_a.0
+                            + (scanline_y - point_a.1) / (point_b.1 - point_a.1)
+                                * (point_b.0 - point_a.0)
+                    }),
+            );
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            for crossing_pair in crossings.chunks_exact(2) {
+                let start_x = (crossing_pair[0] - 0.5).ceil().max(0.0);
+                let end_x = (crossing_pair[1] - 0.5)
+                    .floor()
+                    .min(image_width as f64 - 1.0);
+
+                if start_x > end_x {
+                    continue;
                 }
 
-                last_x = x_pixel;
+                for image_x in start_x as u32..=end_x as u32 {
+                    self.put_pixel(image_x, image_y, color);
+                    filled_pixel_count += 1;
+                }
             }
         }
+
+        filled_pixel_count
     }
+}
 
-    None
+#[derive(Default)]
+struct RenderStats {
+    processed_ways: u32,
+    filled_pixel_count: u32,
 }
 
-/// Runs a flood fill implementation based on the given (x, y) point, filling it until hitting
-/// the outline everywhere
-fn get_flood_filled_pixels(
-    (x, y): (u32, u32),
-    outline_pixels: &HashMap<u32, BTreeSet<u32>>,
-) -> HashSet<(u32, u32)> {
-    // dbg!((x, y), &outline_pixels);
+impl RenderStats {
+    fn log(&self, render_start_instant: Instant, way_count: usize) {
+        let render_duration = render_start_instant.elapsed();
 
-    if !is_inside(&(x, y), outline_pixels) {
-        unreachable!("starting point must be inside");
+        info!(
+            "Rendering took {:.2} s. {:.2} ways/sec.",
+            render_duration.as_secs_f32(),
+            way_count as f64 / (render_duration.as_nanos() as f64 * 1e-9)
+        );
+
+        debug!(
+            "Rasterized {} ways, filled {} pixels of areas.",
+            self.processed_ways, self.filled_pixel_count
+        );
     }
+}
 
-    let mut flood_filled_pixels = HashSet::new();
+/// Saves the given rendered image as a PNG file to `file_path`.
+pub fn save_png(image_buffer: &image::RgbaImage, file_path: &Path) -> image::ImageResult<()> {
+    let save_start_instant = Instant::now();
 
-    // we will put at least 4 more items on the stack during the first loop (assuming that we are inside the outline),
-    // take account for that
-    // after that, it is not really possible before-hand to guess how much capacity we'll need overall
-    let mut stack = Vec::with_capacity(4);
+    image_buffer.save_with_format(file_path, image::ImageFormat::Png)?;
 
-    // use given point as a starting point for flood filling
-    stack.push((x, y));
+    info!(
+        "Image saved successfully to {}, took {:.2} s.",
+        file_path.display(),
+        save_start_instant.elapsed().as_secs_f32()
+    );
 
-    let mut iterations = 0;
+    Ok(())
+}
 
-    while let Some((x, y)) = stack.pop() {
-        iterations += 1;
+/// Encodes the given rendered image as PNG and returns the encoded bytes, e.g. for sending
+/// them over the network without touching the file system.
+pub fn encode_png(image_buffer: &image::RgbaImage) -> image::ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
 
-        if iterations == 100_000 {
-            // TODO: Check whether there is a logic error somewhere that may result into an infinite loop
-            warn!("Giving up flood filling to possibly prevent infinite loop");
-            return flood_filled_pixels;
-        }
+    image_buffer.write_to(
+        &mut std::io::Cursor::new(&mut bytes),
+        image::ImageOutputFormat::Png,
+    )?;
 
-        if flood_filled_pixels.contains(&(x, y)) || !is_inside(&(x, y), outline_pixels) {
-            continue;
-        }
+    Ok(bytes)
+}
 
-        flood_filled_pixels.insert((x, y));
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::num::NonZeroI64;
+
+    use super::{encode_png, ImageBand, Painter, PngPainter};
+    use crate::data::{NodeData, WayData};
+    use crate::mapcss::declaration::MapCssDeclarationList;
+    use crate::mapcss::parser::MapCssParser;
+    use crate::viewport::{OutputSize, ScaleMode};
+    use ab_glyph::FontArc;
+
+    fn node(nid: i64, lat: f64, lon: f64) -> (NonZeroI64, NodeData) {
+        let nid = NonZeroI64::new(nid).unwrap();
+
+        (
+            nid,
+            NodeData {
+                nid,
+                lat,
+                lon,
+                tags: vec![],
+                ways: vec![],
+            },
+        )
+    }
+
+    fn way(wid: i64, nids: &[i64]) -> (NonZeroI64, WayData) {
+        let wid = NonZeroI64::new(wid).unwrap();
+        let refs = nids
+            .iter()
+            .map(|nid| NonZeroI64::new(*nid).unwrap())
+            .collect();
 
-        stack.push((x, y + 1));
-        stack.push((x, y - 1));
-        stack.push((x + 1, y));
-        stack.push((x - 1, y));
+        (wid, WayData::new(wid, vec![], refs))
     }
 
-    flood_filled_pixels
-}
+    #[test]
+    fn test_paint_returns_image_in_memory() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "canvas { fill-color: #000000; } way { color: #ff0000; width: 1; }",
+        )
+        .unwrap();
+
+        let nid_to_node_data = HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.1)]);
+        let wid_to_way_data = HashMap::from([way(1, &[1, 2])]);
+
+        let image = PngPainter::default().paint(
+            OutputSize::new(64, 32, ScaleMode::Fit).unwrap(),
+            MapCssDeclarationList::new(rules),
+            nid_to_node_data,
+            wid_to_way_data,
+            HashMap::new(),
+        );
 
-#[cfg(test)]
-mod tests {
-    use std::collections::{BTreeSet, HashMap};
+        assert_eq!((image.width(), image.height()), (64, 32));
+        // the extent is taller than wide, so the left border is padding
+        assert_eq!(image.get_pixel(0, 0), &image::Rgba([0, 0, 0, 255]));
+        assert!(image
+            .pixels()
+            .any(|pixel| pixel == &image::Rgba([255, 0, 0, 255])));
 
-    use super::is_inside;
+        let png_bytes = encode_png(&image).unwrap();
+        assert_eq!(&png_bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
 
     #[test]
-    fn test_is_inside_simple() {
-        // ^
-        // y
-        // 4
-        // 3
-        // 2   | p |
-        // 1
-        // 0 1 2 3 4 x ->
-        assert!(is_inside(
-            &(3, 2),                                       // = p
-            &HashMap::from([(2, BTreeSet::from([2, 4]))])  // = borders
-        ));
-
-        // ^
-        // y
-        // 4
-        // 3
-        // 2 p |   |
-        // 1
-        // 0 1 2 3 4 x ->
-        assert!(!is_inside(
-            &(1, 2),                                       // = p
-            &HashMap::from([(2, BTreeSet::from([2, 4]))])  // = borders
-        ));
+    fn test_paint_resolves_width_units() {
+        let count_red_pixels = |width: &str| {
+            let (_, rules, _) = MapCssParser::parse_mapcss(&format!(
+                "canvas {{ fill-color: #000000; }} way {{ color: #ff0000; width: {}; }}",
+                width
+            ))
+            .unwrap();
+
+            PngPainter::default()
+                .paint(
+                    OutputSize::new(64, 64, ScaleMode::Fit).unwrap(),
+                    MapCssDeclarationList::new(rules),
+                    HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.0)]),
+                    HashMap::from([way(1, &[1, 2])]),
+                    HashMap::new(),
+                )
+                .pixels()
+                .filter(|pixel| *pixel == &image::Rgba([255, 0, 0, 255]))
+                .count()
+        };
+
+        let one_pixel = count_red_pixels("1px");
+
+        assert!(one_pixel > 0);
+        // ways thinner than a pixel do not vanish
+        assert_eq!(count_red_pixels("0.3"), one_pixel);
+        assert_eq!(count_red_pixels("0"), 0);
+        // the way is ~11 km long, so 1 km is roughly six pixels wide
+        assert!(count_red_pixels("1000m") > 4 * one_pixel);
     }
 
     #[test]
-    fn test_is_inside_complex() {
-        // ^
-        // y
-        // 4
-        // 3 |     |
-        // 2 q | p |
-        // 1 | o |   | |
-        // 0 1 2 3 4 5 6 x ->
-        let outline_representation = HashMap::from([
-            (1, BTreeSet::from([1, 3, 5, 6])),
-            (2, BTreeSet::from([2, 4])),
-            (3, BTreeSet::from([1, 4])),
-        ]);
-        let q = ((1, 2), false);
-        let p = ((3, 2), true);
-        let o = ((2, 1), true);
-
-        for (coords, expected) in [q, p, o] {
-            assert_eq!(is_inside(&coords, &outline_representation), expected);
-        }
+    fn test_paint_draws_layers_in_z_index_order() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "canvas { fill-color: #000000; }
+            way { color: #ff0000; width: 1; z-index: 2; }
+            way::casing { color: #0000ff; width: 5; z-index: 1; }",
+        )
+        .unwrap();
+
+        let image = PngPainter::default().paint(
+            OutputSize::new(64, 64, ScaleMode::Fit).unwrap(),
+            MapCssDeclarationList::new(rules),
+            HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.0)]),
+            HashMap::from([way(1, &[1, 2])]),
+            HashMap::new(),
+        );
+
+        // the line is drawn on top of its wider casing
+        let count_pixels = |color| image.pixels().filter(|pixel| *pixel == &color).count();
+        assert!(count_pixels(image::Rgba([255, 0, 0, 255])) > 0);
+        assert!(count_pixels(image::Rgba([0, 0, 255, 255])) > 0);
+    }
+
+    #[test]
+    fn test_paint_skips_nodes_missing_from_the_extract() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "canvas { fill-color: #000000; } way { color: #ff0000; width: 1; }",
+        )
+        .unwrap();
+
+        // the nodes 3 and 4 are not part of the extract
+        let image = PngPainter::default().paint(
+            OutputSize::new(64, 64, ScaleMode::Fit).unwrap(),
+            MapCssDeclarationList::new(rules),
+            HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.0)]),
+            HashMap::from([way(1, &[1, 3, 2]), way(2, &[3, 4])]),
+            HashMap::new(),
+        );
+
+        assert!(image
+            .pixels()
+            .any(|pixel| pixel == &image::Rgba([255, 0, 0, 255])));
+    }
+
+    #[test]
+    fn test_paint_fills_closed_ways_only_with_fill_color() {
+        let count_green_pixels = |style: &str| {
+            let (_, rules, _) = MapCssParser::parse_mapcss(style).unwrap();
+
+            PngPainter::default()
+                .paint(
+                    OutputSize::new(64, 64, ScaleMode::Fit).unwrap(),
+                    MapCssDeclarationList::new(rules),
+                    HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.0), node(3, 47.1, 9.1)]),
+                    HashMap::from([way(1, &[1, 2, 3, 1])]),
+                    HashMap::new(),
+                )
+                .pixels()
+                .filter(|pixel| *pixel == &image::Rgba([0, 255, 0, 255]))
+                .count()
+        };
+
+        let outline =
+            count_green_pixels("canvas { fill-color: #000000; } way { color: #00ff00; width: 1; }");
+        let area = count_green_pixels(
+            "canvas { fill-color: #000000; } way { color: #00ff00; fill-color: #00ff00; width: 1; }",
+        );
+
+        assert!(outline > 0);
+        assert!(area > 4 * outline);
+    }
+
+    #[test]
+    fn test_paint_places_labels() {
+        // the demo font of the ttf-parser crate draws a box for every character but A
+        let font = FontArc::try_from_slice(include_bytes!("../include/demo.ttf")).unwrap();
+
+        // the columns and rows of the image covered by the label of the village in the center
+        let label_bounds = |placement: &str| {
+            let (_, rules, _) = MapCssParser::parse_mapcss(&format!(
+                "canvas {{ fill-color: #000000; }}
+                node[place] {{ text: \"BB B\"; text-color: #ff0000; font-size: 10; {} }}",
+                placement
+            ))
+            .unwrap();
+
+            let mut painter = PngPainter::default();
+            painter.set_font(font.clone());
+
+            let mut village = node(3, 47.05, 9.05);
+            village.1.tags.push(("place".into(), "village".into()));
+
+            // the way only spans the extent, it has no width to be drawn with
+            let image = painter.paint(
+                OutputSize::new(64, 64, ScaleMode::Fit).unwrap(),
+                MapCssDeclarationList::new(rules),
+                HashMap::from([node(1, 47.0, 9.0), node(2, 47.1, 9.1), village]),
+                HashMap::from([way(1, &[1, 2])]),
+                HashMap::new(),
+            );
+
+            let (xs, ys): (Vec<u32>, Vec<u32>) = image
+                .enumerate_pixels()
+                .filter(|(_, _, pixel)| pixel[0] > 0)
+                .map(|(x, y, _)| (x, y))
+                .unzip();
+
+            (
+                *xs.iter().min().unwrap()..=*xs.iter().max().unwrap(),
+                *ys.iter().min().unwrap()..=*ys.iter().max().unwrap(),
+            )
+        };
+
+        let (columns, rows) = label_bounds("");
+        // centered on the node by default
+        assert!(columns.contains(&32) && rows.contains(&32));
+
+        let (right_columns, below_rows) =
+            label_bounds("text-anchor-horizontal: right; text-anchor-vertical: below;");
+        assert!(*right_columns.start() >= 31 && *below_rows.start() >= 31);
+
+        let (left_columns, above_rows) =
+            label_bounds("text-anchor-horizontal: left; text-anchor-vertical: above;");
+        assert!(*left_columns.end() <= 33 && *above_rows.end() <= 33);
+
+        let (_, offset_rows) = label_bounds("text-anchor-vertical: below; text-offset-y: 10;");
+        assert_eq!(*offset_rows.start(), *below_rows.start() + 10);
+
+        // the words are broken into two lines
+        let (wrapped_columns, wrapped_rows) = label_bounds("text-wrap-width: 10;");
+        assert!(wrapped_columns.end() - wrapped_columns.start() < columns.end() - columns.start());
+        assert!(wrapped_rows.end() - wrapped_rows.start() > rows.end() - rows.start());
+
+        let (semicolon_columns, _) =
+            label_bounds("text-wrap-width: 10; text-wrap-character: \";\";");
+        assert_eq!(semicolon_columns, columns);
+    }
+
+    #[test]
+    fn test_paint_to_writer_in_bands_matches_paint() {
+        let (_, rules, _) = MapCssParser::parse_mapcss(
+            "canvas { fill-color: #000000; } way { color: #ff0000; fill-color: #00ff00; width: 3; }",
+        )
+        .unwrap();
+
+        let nid_to_node_data =
+            HashMap::from([node(1, 47.0, 9.0), node(2, 47.3, 9.1), node(3, 47.1, 9.4)]);
+        let wid_to_way_data = HashMap::from([way(1, &[1, 2, 3, 1])]);
+
+        let output_size = OutputSize::new(100, 300, ScaleMode::Fill).unwrap();
+
+        let image = PngPainter::default().paint(
+            output_size,
+            MapCssDeclarationList::new(rules.clone()),
+            nid_to_node_data.clone(),
+            wid_to_way_data.clone(),
+            HashMap::new(),
+        );
+
+        // a budget this small results in bands of the minimal height
+        let mut png_bytes = Vec::new();
+        PngPainter::with_band_memory_budget(1)
+            .paint_to_writer(
+                output_size,
+                MapCssDeclarationList::new(rules),
+                nid_to_node_data,
+                wid_to_way_data,
+                HashMap::new(),
+                &mut png_bytes,
+            )
+            .unwrap();
+
+        let streamed_image = image::load_from_memory(&png_bytes).unwrap().to_rgba8();
+
+        assert!(image.height() > super::IMAGE_PART_SIZE);
+        assert_eq!(image, streamed_image);
+    }
+
+    #[test]
+    fn test_fill_polygon_within_band() {
+        let background = image::Rgba([0, 0, 0, 255]);
+        let fill = image::Rgba([0, 255, 0, 255]);
+
+        // the band holds the image rows 2 to 4
+        let mut band = ImageBand {
+            top: 2,
+            buffer: image::ImageBuffer::from_pixel(8, 3, background),
+        };
+
+        // a square from (1, 1) to (5, 7) with a square hole from (2, 3) to (4, 4)
+        let filled_pixel_count = band.fill_polygon(
+            &[
+                (1.0, 1.0),
+                (5.0, 1.0),
+                (5.0, 7.0),
+                (1.0, 7.0),
+                (1.0, 1.0),
+                (2.0, 3.0),
+                (4.0, 3.0),
+                (4.0, 4.0),
+                (2.0, 4.0),
+                (2.0, 3.0),
+            ],
+            2..=4,
+            fill,
+        );
+
+        let rows: Vec<String> = (0..3)
+            .map(|y| {
+                (0..8)
+                    .map(|x| match band.buffer.get_pixel(x, y) == &fill {
+                        true => '#',
+                        false => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(rows, [".####...", ".#..#...", ".####..."]);
+        assert_eq!(filled_pixel_count, 10);
     }
 }
@@ -0,0 +1,291 @@
+use crate::data::NodeData;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// The width and height of a Web Mercator tile in pixels, which defines what a zoom level is.
+const TILE_SIZE: f64 = 256.0;
+
+/// The circumference of the earth at the equator in meters, as used by Web Mercator.
+const EARTH_CIRCUMFERENCE: f64 = 40_075_016.686;
+
+/// The zoom level used when the data has no extent at all (e.g. a single node).
+const DEFAULT_ZOOM_LEVEL: f64 = 18.0;
+
+/// The requested size of the rendered image, which is never empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputSize {
+    width: u32,
+    height: u32,
+    mode: ScaleMode,
+}
+
+impl OutputSize {
+    pub fn new(width: u32, height: u32, mode: ScaleMode) -> Result<OutputSize, EmptyOutputSize> {
+        if width == 0 || height == 0 {
+            return Err(EmptyOutputSize { width, height });
+        }
+
+        Ok(OutputSize {
+            width,
+            height,
+            mode,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+}
+
+/// An output size with a width or height of zero was requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmptyOutputSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Error for EmptyOutputSize {}
+
+impl fmt::Display for EmptyOutputSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The output size {}x{} must not be empty.",
+            self.width, self.height
+        )
+    }
+}
+
+/// Determines how the extent of the data is mapped onto an image with a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// Shows the whole extent, padding the remaining space of the image with the background.
+    #[default]
+    Fit,
+    /// Covers the whole image with the extent, cropping whatever does not fit.
+    Fill,
+}
+
+/// The geographic extent of some data, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    /// Returns the smallest bounding box containing all given nodes, or `None` if there are none.
+    pub fn from_nodes<'a>(nodes: impl IntoIterator<Item = &'a NodeData>) -> Option<BoundingBox> {
+        nodes.into_iter().fold(None, |bounding_box, node| {
+            Some(match bounding_box {
+                None => BoundingBox {
+                    min_lat: node.lat,
+                    max_lat: node.lat,
+                    min_lon: node.lon,
+                    max_lon: node.lon,
+                },
+                Some(bounding_box) => BoundingBox {
+                    min_lat: bounding_box.min_lat.min(node.lat),
+                    max_lat: bounding_box.max_lat.max(node.lat),
+                    min_lon: bounding_box.min_lon.min(node.lon),
+                    max_lon: bounding_box.max_lon.max(node.lon),
+                },
+            })
+        })
+    }
+}
+
+/// Maps geographic coordinates onto the pixels of the output image using the Web Mercator
+/// projection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    width: u32,
+    height: u32,
+    /// pixels per unit of the projected world, which spans `[0, 1]` in both directions
+    scale: f64,
+    /// the projected world coordinates of the image's center
+    center: (f64, f64),
+}
+
+impl Viewport {
+    /// Creates a viewport that shows `extent` centered on an image of the given size.
+    pub fn new(extent: &BoundingBox, output_size: OutputSize) -> Viewport {
+        let (min_x, min_y) = project_to_world(extent.max_lat, extent.min_lon);
+        let (max_x, max_y) = project_to_world(extent.min_lat, extent.max_lon);
+
+        let horizontal_scale = output_size.width() as f64 / (max_x - min_x);
+        let vertical_scale = output_size.height() as f64 / (max_y - min_y);
+
+        // a zero extent results in an infinite scale in that direction
+        let scale = match (horizontal_scale.is_finite(), vertical_scale.is_finite()) {
+            (true, true) => match output_size.mode {
+                ScaleMode::Fit => horizontal_scale.min(vertical_scale),
+                ScaleMode::Fill => horizontal_scale.max(vertical_scale),
+            },
+            (true, false) => horizontal_scale,
+            (false, true) => vertical_scale,
+            (false, false) => TILE_SIZE * DEFAULT_ZOOM_LEVEL.exp2(),
+        };
+
+        Viewport {
+            width: output_size.width,
+            height: output_size.height,
+            scale,
+            center: ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the (fractional) pixel coordinates of the given location, which may lie outside
+    /// of the image.
+    pub fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let (world_x, world_y) = project_to_world(lat, lon);
+
+        (
+            (world_x - self.center.0) * self.scale + self.width as f64 / 2.0,
+            (world_y - self.center.1) * self.scale + self.height as f64 / 2.0,
+        )
+    }
+
+    /// Returns the location of the given pixel coordinates as latitude and longitude.
+    pub fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        let world_x = (x - self.width as f64 / 2.0) / self.scale + self.center.0;
+        let world_y = (y - self.height as f64 / 2.0) / self.scale + self.center.1;
+
+        (
+            (PI * (1.0 - 2.0 * world_y)).sinh().atan().to_degrees(),
+            world_x * 360.0 - 180.0,
+        )
+    }
+
+    /// Returns how many meters on the ground one pixel covers at the given latitude.
+    pub fn meters_per_pixel(&self, lat: f64) -> f64 {
+        EARTH_CIRCUMFERENCE * lat.to_radians().cos() / self.scale
+    }
+
+    /// Returns the (fractional) Web Mercator zoom level this viewport corresponds to.
+    pub fn zoom_level(&self) -> f64 {
+        (self.scale / TILE_SIZE).log2()
+    }
+}
+
+/// Projects the given location into Web Mercator world coordinates in the range `[0, 1]`,
+/// with the y axis pointing south.
+fn project_to_world(lat: f64, lon: f64) -> (f64, f64) {
+    let lat = lat.to_radians();
+
+    (
+        (lon + 180.0) / 360.0,
+        (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundingBox, EmptyOutputSize, OutputSize, ScaleMode, Viewport};
+
+    const LIECHTENSTEIN: BoundingBox = BoundingBox {
+        min_lat: 47.04,
+        max_lat: 47.27,
+        min_lon: 9.47,
+        max_lon: 9.64,
+    };
+
+    #[test]
+    fn test_empty_output_size_is_rejected() {
+        assert_eq!(
+            OutputSize::new(0, 1080, ScaleMode::Fit),
+            Err(EmptyOutputSize {
+                width: 0,
+                height: 1080
+            })
+        );
+        assert!(OutputSize::new(1920, 0, ScaleMode::Fill).is_err());
+        assert!(OutputSize::new(1, 1, ScaleMode::Fill).is_ok());
+    }
+
+    #[test]
+    fn test_fit_keeps_the_whole_extent_visible() {
+        let viewport = Viewport::new(
+            &LIECHTENSTEIN,
+            OutputSize::new(1920, 1080, ScaleMode::Fit).unwrap(),
+        );
+
+        let (left, top) = viewport.project(LIECHTENSTEIN.max_lat, LIECHTENSTEIN.min_lon);
+        let (right, bottom) = viewport.project(LIECHTENSTEIN.min_lat, LIECHTENSTEIN.max_lon);
+
+        // the extent is taller than wide, so it touches the top and bottom and is padded
+        // horizontally
+        assert!((top - 0.0).abs() < 1e-6);
+        assert!((bottom - 1080.0).abs() < 1e-6);
+        assert!(left > 0.0 && right < 1920.0);
+        assert!(((left + right) / 2.0 - 960.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fill_covers_the_whole_image() {
+        let viewport = Viewport::new(
+            &LIECHTENSTEIN,
+            OutputSize::new(1920, 1080, ScaleMode::Fill).unwrap(),
+        );
+
+        let (left, top) = viewport.project(LIECHTENSTEIN.max_lat, LIECHTENSTEIN.min_lon);
+        let (right, bottom) = viewport.project(LIECHTENSTEIN.min_lat, LIECHTENSTEIN.max_lon);
+
+        assert!((left - 0.0).abs() < 1e-6);
+        assert!((right - 1920.0).abs() < 1e-6);
+        assert!(top < 0.0 && bottom > 1080.0);
+    }
+
+    #[test]
+    fn test_zoom_level() {
+        // one tile showing the whole world is zoom level 0
+        let world = BoundingBox {
+            min_lat: -85.0511287798,
+            max_lat: 85.0511287798,
+            min_lon: -180.0,
+            max_lon: 180.0,
+        };
+
+        let viewport = Viewport::new(&world, OutputSize::new(256, 256, ScaleMode::Fit).unwrap());
+        assert!(viewport.zoom_level().abs() < 1e-6);
+
+        let viewport = Viewport::new(&world, OutputSize::new(1024, 1024, ScaleMode::Fit).unwrap());
+        assert!((viewport.zoom_level() - 2.0).abs() < 1e-6);
+
+        // ~39 km per pixel at zoom level 2 at the equator
+        assert!((viewport.meters_per_pixel(0.0) - 39_135.76).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_unproject_reverses_project() {
+        let viewport = Viewport::new(
+            &LIECHTENSTEIN,
+            OutputSize::new(800, 600, ScaleMode::Fill).unwrap(),
+        );
+
+        let (x, y) = viewport.project(47.14, 9.52);
+        let (lat, lon) = viewport.unproject(x, y);
+
+        assert!((lat - 47.14).abs() < 1e-9);
+        assert!((lon - 9.52).abs() < 1e-9);
+    }
+}